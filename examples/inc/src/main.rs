@@ -34,7 +34,7 @@ impl Component for Model {
         gtk! {
             <Application::new_unwrap(Some("camp.lol.updog"), ApplicationFlags::empty())>
                 <Window border_width=20 on destroy=|_| Message::Exit>
-                    <HeaderBar title="inc!" subtitle="AD ASTRA AD INFINITVM"
+                    <HeaderBar Window::titlebar=true title="inc!" subtitle="AD ASTRA AD INFINITVM"
                                show_close_button=true />
                     <Box spacing=10 halign=Align::Center>
                         <Label label=self.counter.to_string() />
@@ -5,7 +5,7 @@ use vgtk::lib::gio::{ActionExt, ApplicationFlags, File, FileExt, SimpleAction};
 use vgtk::lib::glib::Error;
 use vgtk::lib::gtk::prelude::*;
 use vgtk::lib::gtk::*;
-use vgtk::{ext::*, gtk, gtk_if, on_signal, Component, UpdateAction, VNode};
+use vgtk::{ext::*, gtk, on_signal, Component, UpdateAction, VNode};
 
 use strum_macros::{Display, EnumIter};
 
@@ -89,11 +89,9 @@ impl Model {
                 <Box spacing=10 orientation=Orientation::Horizontal Box::expand=false>
                     <Label label=self.left_label()/>
                     <@Radio<Filter> active=self.filter Box::center_widget=true on changed=|filter| Msg::Filter { filter } />
-                    {
-                        gtk_if!(self.filter(Filter::Completed).count() > 0 => {
-                            <Button label="Clear completed" Box::pack_type=PackType::End
-                                    on clicked=|_| Msg::ClearCompleted/>
-                        })
+                    if self.filter(Filter::Completed).count() > 0 {
+                        <Button label="Clear completed" Box::pack_type=PackType::End
+                                on clicked=|_| Msg::ClearCompleted/>
                     }
                 </Box>
             </Box>
@@ -261,7 +259,7 @@ impl Component for Model {
                     <SimpleAction::new("save-as", None) ApplicationWindow::accels=["<Ctrl><Shift>s"].as_ref()
                                                         enabled=true on activate=|_, _| Msg::MenuSaveAs/>
 
-                    <HeaderBar title=format!("TodoMVC - {}{}", title, clean) subtitle="wtf do we do now" show_close_button=true>
+                    <HeaderBar Window::titlebar=true title=format!("TodoMVC - {}{}", title, clean) subtitle="wtf do we do now" show_close_button=true>
                         <MenuButton HeaderBar::pack_type=PackType::End @MenuButtonExt::direction=ArrowType::Down relief=ReliefStyle::None
                                     image="open-menu-symbolic">
                             <Menu::from_model(&main_menu)/>
@@ -57,7 +57,7 @@ where
             <Box orientation=Orientation::Horizontal spacing=10>
                 { Enum::iter().map(|label| {
                     gtk!{
-                        <ToggleButton label=label.to_string() active=label == self.active
+                        <ToggleButton label=label.to_string() live:active=label == self.active
                                       on toggled=|_| RadioMsg::Selected(label)/>
                     }
                 }) }
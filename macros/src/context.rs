@@ -1,6 +1,6 @@
 use std::fmt::{Debug, Error, Formatter};
 
-use proc_macro2::{Group, Ident};
+use proc_macro2::{Group, Ident, Literal};
 
 use crate::lexer::{Token, Tokens};
 
@@ -16,13 +16,76 @@ pub struct GtkWidget {
 pub struct GtkComponent {
     pub name: Tokens,
     pub attributes: Vec<Attribute>,
+    pub children: Vec<GtkElement>,
 }
 
 #[derive(Debug, Clone)]
 pub enum GtkElement {
     Widget(GtkWidget),
     Component(GtkComponent),
+    Fragment(Vec<GtkElement>),
     Block(Group),
+    If(GtkIf),
+    Match(GtkMatch),
+    For(GtkFor),
+}
+
+/// An `if` element, with a condition and children for each `if` and `else if`
+/// branch, and the children of the final `else`, if any.
+#[derive(Debug, Clone)]
+pub struct GtkIf {
+    pub branches: Vec<(Tokens, Vec<GtkElement>)>,
+    pub otherwise: Option<Vec<GtkElement>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GtkMatch {
+    pub expr: Tokens,
+    pub arms: Vec<GtkMatchArm>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GtkMatchArm {
+    pub pattern: Tokens,
+    pub guard: Option<Tokens>,
+    pub body: Vec<GtkElement>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GtkFor {
+    pub pattern: Tokens,
+    pub iter: Tokens,
+    pub key: Option<Tokens>,
+    pub body: Vec<GtkElement>,
+}
+
+/// The qualifier in front of a property name, as in `live:text=...`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Qualifier {
+    /// Compare against the widget's current value rather than the last value we set.
+    Live,
+    /// Set the property by its GObject name rather than through its setter method.
+    Prop,
+    /// Give the property to the object's constructor by its GObject name, and
+    /// rebuild the object when it changes.
+    Construct,
+    /// Compare against the widget's current value like `Live`, and report the
+    /// user's changes back through a handler. Only used by `Attribute::Binding`.
+    Bind,
+    /// Add the style class of that name to the widget while the value is true.
+    Class,
+}
+
+impl Qualifier {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Qualifier::Live => "live",
+            Qualifier::Prop => "prop",
+            Qualifier::Construct => "construct",
+            Qualifier::Bind => "bind",
+            Qualifier::Class => "class",
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -32,6 +95,10 @@ pub enum Attribute {
         parent: Tokens,
         name: Ident,
         value: Tokens,
+        qualifier: Option<Qualifier>,
+        /// Written as `name?=value`: only set the property when the value is
+        /// `Some`.
+        optional: bool,
     },
     Handler {
         name: Ident,
@@ -39,6 +106,19 @@ pub enum Attribute {
         args: Tokens,
         body: Tokens,
     },
+    NamedHandler {
+        name: Literal,
+        async_keyword: Option<Token>,
+        args: Tokens,
+        body: Tokens,
+    },
+    Binding {
+        name: Ident,
+        value: Tokens,
+        message: Tokens,
+    },
+    /// A `..expr` attribute, providing attributes not given explicitly.
+    Spread { value: Tokens },
 }
 
 fn stringify_attr_value(token: &Token) -> String {
@@ -58,6 +138,8 @@ impl Debug for Attribute {
                 parent,
                 name,
                 value,
+                qualifier,
+                optional,
             } => {
                 let attrs: Vec<String> = value.iter().map(stringify_attr_value).collect();
                 let mut name = name.to_string();
@@ -66,34 +148,71 @@ impl Debug for Attribute {
                     let qual = if *child { "" } else { "@" };
                     name = format!("{}{}{}", qual, parent_path, name);
                 }
-                write!(f, "( {} = {} )", name, attrs.join(", "))
+                if let Some(qualifier) = qualifier {
+                    name = format!("{}:{}", qualifier.as_str(), name);
+                }
+                let eq = if *optional { "?=" } else { "=" };
+                write!(f, "( {} {} {} )", name, eq, attrs.join(", "))
             }
             Attribute::Handler {
                 name,
                 async_keyword,
                 args,
                 body,
+            } => fmt_handler(f, &name.to_string(), async_keyword, args, body),
+            Attribute::NamedHandler {
+                name,
+                async_keyword,
+                args,
+                body,
+            } => fmt_handler(f, &name.to_string(), async_keyword, args, body),
+            Attribute::Binding {
+                name,
+                value,
+                message,
             } => {
-                let args: Vec<String> = args.iter().map(stringify_attr_value).collect();
-                let attrs: Vec<String> = body.iter().map(stringify_attr_value).collect();
-                let async_keyword = if async_keyword.is_some() {
-                    "async "
-                } else {
-                    ""
-                };
+                let value: Vec<String> = value.iter().map(stringify_attr_value).collect();
+                let message: Vec<String> = message.iter().map(stringify_attr_value).collect();
                 write!(
                     f,
-                    "( {} = {}{} {} )",
-                    name.to_string(),
-                    async_keyword,
-                    args.join(", "),
-                    attrs.join(", ")
+                    "( bind:{} = {} => {} )",
+                    name,
+                    value.join(", "),
+                    message.join(", ")
                 )
             }
+            Attribute::Spread { value } => {
+                let value: Vec<String> = value.iter().map(stringify_attr_value).collect();
+                write!(f, "( ..{} )", value.join(", "))
+            }
         }
     }
 }
 
+fn fmt_handler(
+    f: &mut Formatter<'_>,
+    name: &str,
+    async_keyword: &Option<Token>,
+    args: &[Token],
+    body: &[Token],
+) -> Result<(), Error> {
+    let args: Vec<String> = args.iter().map(stringify_attr_value).collect();
+    let attrs: Vec<String> = body.iter().map(stringify_attr_value).collect();
+    let async_keyword = if async_keyword.is_some() {
+        "async "
+    } else {
+        ""
+    };
+    write!(
+        f,
+        "( {} = {}{} {} )",
+        name,
+        async_keyword,
+        args.join(", "),
+        attrs.join(", ")
+    )
+}
+
 impl PartialEq<(&str, &str)> for Attribute {
     fn eq(&self, other: &(&str, &str)) -> bool {
         match self {
@@ -102,6 +221,7 @@ impl PartialEq<(&str, &str)> for Attribute {
                 parent,
                 name,
                 value,
+                ..
             } => {
                 let mut name = name.to_string();
                 if !parent.is_empty() {
@@ -114,6 +234,15 @@ impl PartialEq<(&str, &str)> for Attribute {
             Attribute::Handler { name, .. } => {
                 format!("on {}", name.to_string()) == other.0 // FIXME: only compares handler name
             }
+            Attribute::NamedHandler { name, .. } => {
+                format!("on {}", name) == other.0 // FIXME: only compares handler name
+            }
+            Attribute::Binding { name, value, .. } => {
+                format!("bind:{}", name) == other.0 && stringify_attr_value(&value[0]) == other.1
+            }
+            Attribute::Spread { value } => {
+                other.0 == ".." && stringify_attr_value(&value[0]) == other.1
+            }
         }
     }
 }
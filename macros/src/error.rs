@@ -1,14 +1,41 @@
 use crate::lexer::{to_stream, Token, Tokens};
 use lalrpop_util::ParseError::*;
-use proc_macro2::TokenStream;
+use proc_macro2::{Group, Ident, TokenStream};
 use quote::{quote, quote_spanned};
 
 pub type ParseError = lalrpop_util::ParseError<usize, Token, RsxParseError>;
 
 #[derive(Debug)]
 pub enum RsxParseError {
-    TagMismatch { open: Tokens, close: Tokens },
-    UnexpectedConstructor { name: Tokens, args: Token },
+    TagMismatch {
+        open: Tokens,
+        close: Tokens,
+        component: bool,
+    },
+    UnexpectedConstructor {
+        name: Tokens,
+        args: Token,
+    },
+    UnknownQualifier {
+        qualifier: Ident,
+    },
+    InvalidKey {
+        name: Ident,
+    },
+    InvalidBinding {
+        name: Ident,
+        reason: &'static str,
+    },
+    InvalidOptional {
+        name: Ident,
+        reason: &'static str,
+    },
+    /// An error from parsing the contents of a block, like the body of an `if`.
+    Nested {
+        group: Group,
+        tokens: Tokens,
+        error: std::boxed::Box<ParseError>,
+    },
 }
 
 fn pprint_token(token: &str) -> &str {
@@ -66,12 +93,20 @@ pub fn parse_error(input: &[Token], error: &ParseError) -> TokenStream {
             quote_spanned! { span => compile_error! { "superfluous token" }}
         }
         User {
-            error: RsxParseError::TagMismatch { open, close },
+            error:
+                RsxParseError::TagMismatch {
+                    open,
+                    close,
+                    component,
+                },
         } => {
             let close_span = close[0].span();
+            let sigil = if *component { "@" } else { "" };
             let close_msg = format!(
-                "expected closing tag `</{}>`, found `</{}>`",
+                "expected closing tag `</{}{}>`, found `</{}{}>`",
+                sigil,
                 to_stream(open),
+                sigil,
                 to_stream(close)
             );
             let close_error = quote_spanned! {close_span=>
@@ -98,5 +133,54 @@ pub fn parse_error(input: &[Token], error: &ParseError) -> TokenStream {
                 compile_error! { #error_msg }
             }
         }
+        User {
+            error: RsxParseError::UnknownQualifier { qualifier },
+        } => {
+            let error_msg = format!(
+                "unknown attribute qualifier `{}:` - the qualifiers are `live:`, `prop:`, `construct:`, `bind:` and `class:`",
+                qualifier
+            );
+            quote_spanned! { qualifier.span() =>
+                compile_error! { #error_msg }
+            }
+        }
+        User {
+            error: RsxParseError::InvalidKey { name },
+        } => {
+            let error_msg = format!("expected `key`, found `{}`", name);
+            quote_spanned! { name.span() =>
+                compile_error! { #error_msg }
+            }
+        }
+        User {
+            error: RsxParseError::InvalidBinding { name, reason },
+        }
+        | User {
+            error: RsxParseError::InvalidOptional { name, reason },
+        } => {
+            quote_spanned! { name.span() =>
+                compile_error! { #reason }
+            }
+        }
+        User {
+            error:
+                RsxParseError::Nested {
+                    group,
+                    tokens,
+                    error,
+                },
+        } => match &**error {
+            UnrecognizedEOF { expected, .. } => {
+                let span = group.span_close();
+                let msg = format!(
+                    "unexpected end of block; missing {}",
+                    pprint_tokens(expected)
+                );
+                quote_spanned! { span =>
+                    compile_error! { #msg }
+                }
+            }
+            error => parse_error(tokens, error),
+        },
     }
 }
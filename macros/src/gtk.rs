@@ -1,13 +1,118 @@
-use proc_macro2::{Group, Ident, Literal, TokenStream};
+use proc_macro2::{Ident, Literal, Span, TokenStream};
 use quote::{quote, quote_spanned};
 
-use crate::context::{Attribute, GtkComponent, GtkElement, GtkWidget};
-use crate::lexer::{to_stream, Token};
+use crate::context::{
+    Attribute, GtkComponent, GtkElement, GtkFor, GtkIf, GtkMatch, GtkWidget, Qualifier,
+};
+use crate::lexer::{to_stream, Token, Tokens};
 
 fn to_string_literal<S: ToString>(s: S) -> Literal {
     Literal::string(&s.to_string())
 }
 
+fn is_special(attribute: &Attribute, special: &str) -> bool {
+    match attribute {
+        Attribute::Property {
+            child: false,
+            parent,
+            name,
+            qualifier: None,
+            ..
+        } => parent.is_empty() && name == special,
+        _ => false,
+    }
+}
+
+fn is_key(attribute: &Attribute) -> bool {
+    is_special(attribute, "key")
+}
+
+fn is_ref(attribute: &Attribute) -> bool {
+    is_special(attribute, "ref")
+}
+
+fn is_class(attribute: &Attribute) -> bool {
+    is_special(attribute, "class")
+}
+
+fn is_style(attribute: &Attribute) -> bool {
+    is_special(attribute, "style")
+}
+
+fn is_optional(attribute: &Attribute) -> bool {
+    match attribute {
+        Attribute::Property { optional, .. } => *optional,
+        _ => false,
+    }
+}
+
+/// Find the `..expr` attribute of an element, if it has one.
+///
+/// Only one is allowed, so a second one is an error.
+fn find_spread(attributes: &[Attribute]) -> Result<Option<&Tokens>, TokenStream> {
+    let mut spreads = attributes.iter().filter_map(|attribute| match attribute {
+        Attribute::Spread { value } => Some(value),
+        _ => None,
+    });
+    let spread = spreads.next();
+    if let Some(value) = spreads.next() {
+        let span = value[0].span();
+        return Err(quote_spanned! {span =>
+            compile_error! { "an element can only have one `..` attribute" }
+        });
+    }
+    Ok(spread)
+}
+
+/// Expand an attribute written as `name?=value`, which only takes effect when
+/// the value is `Some`. `expand` is given the value inside the `Some`.
+fn expand_optional(value: &[Token], expand: impl FnOnce(&[Token]) -> TokenStream) -> TokenStream {
+    let unwrapped = Ident::new("optional_value", value[0].span());
+    let body = expand(&Tokens::from(unwrapped.clone()));
+    let value = to_stream(value);
+    quote!(
+        if let Some(#unwrapped) = #value {
+            #body
+        }
+    )
+}
+
+/// Expand a property written as `name?=value` and push it onto `list`, giving
+/// it an `unset` which resets the property to its default value when it's
+/// `None` after having been set. `expand` is given the value inside the `Some`
+/// and expands the property itself.
+fn expand_optional_property(
+    list: TokenStream,
+    child: bool,
+    name: &Ident,
+    value: &[Token],
+    expand: impl FnOnce(&[Token]) -> TokenStream,
+) -> TokenStream {
+    let gobject_name = to_string_literal(name.to_string().replace('_', "-"));
+    let unset = if child {
+        quote!(move |object: &vgtk::lib::glib::Object, parent: Option<&vgtk::lib::glib::Object>, _previous: &dyn std::any::Any| {
+            vgtk::properties::reset_child_property_by_name(object, parent, #gobject_name)
+        })
+    } else {
+        quote!(move |object: &vgtk::lib::glib::Object, _parent: Option<&vgtk::lib::glib::Object>, _previous: &dyn std::any::Any| {
+            vgtk::properties::reset_property_by_name(object, #gobject_name)
+        })
+    };
+    expand_optional(value, |value| {
+        let prop = expand(value);
+        quote!(
+            let mut property = #prop;
+            property.unset = Some(std::rc::Rc::new(#unset));
+            #list.push(property);
+        )
+    })
+}
+
+fn expand_key(value: &[Token]) -> TokenStream {
+    let value = to_stream(value);
+    quote!(Some(std::string::ToString::to_string(&(#value))))
+}
+
 fn count_attributes(attributes: &[Attribute]) -> (usize, usize, usize) {
     let mut props = 0;
     let mut child_props = 0;
@@ -21,7 +126,12 @@ fn count_attributes(attributes: &[Attribute]) -> (usize, usize, usize) {
                     props += 1
                 }
             }
-            Attribute::Handler { .. } => handlers += 1,
+            Attribute::Handler { .. } | Attribute::NamedHandler { .. } => handlers += 1,
+            Attribute::Binding { .. } => {
+                props += 1;
+                handlers += 1;
+            }
+            Attribute::Spread { .. } => {}
         }
     }
     (props, child_props, handlers)
@@ -31,10 +141,221 @@ pub fn expand_gtk(gtk: &GtkElement) -> TokenStream {
     match gtk {
         GtkElement::Widget(widget) => expand_widget(widget),
         GtkElement::Component(component) => expand_component(component),
+        GtkElement::Fragment(children) => expand_fragment(children),
         GtkElement::Block(_block) => panic!("blocks not allowed in this position"),
+        GtkElement::If(_) | GtkElement::Match(_) | GtkElement::For(_) => {
+            expand_fragment(std::slice::from_ref(gtk))
+        }
+    }
+}
+
+pub fn expand_fragment(children: &[GtkElement]) -> TokenStream {
+    let children = expand_children(children);
+    quote!({
+        use vgtk::vnode::VNode;
+        use std::vec::Vec;
+        let mut children = Vec::new();
+        #children
+        VNode::Fragment(children)
+    })
+}
+
+fn expand_children<'a>(children: impl IntoIterator<Item = &'a GtkElement>) -> TokenStream {
+    let mut out = TokenStream::new();
+    for child in children {
+        match child {
+            GtkElement::Block(block) => out.extend(quote!(
+                children.extend(#block);
+            )),
+            GtkElement::If(gtk) => out.extend(expand_if(gtk)),
+            GtkElement::Match(gtk) => out.extend(expand_match(gtk)),
+            GtkElement::For(gtk) => out.extend(expand_for(gtk)),
+            child => {
+                let child = expand_gtk(child);
+                out.extend(quote!(
+                    children.push(#child);
+                ))
+            }
+        }
+    }
+    out
+}
+
+// Control flow elements expand into the equivalent Rust statements, which push
+// their children onto the `children` list of the enclosing element.
+
+fn expand_if(gtk: &GtkIf) -> TokenStream {
+    let mut out = TokenStream::new();
+    for (index, (condition, children)) in gtk.branches.iter().enumerate() {
+        if index > 0 {
+            out.extend(quote!(else));
+        }
+        let condition = to_stream(condition);
+        let children = expand_children(children);
+        out.extend(quote!(
+            if #condition {
+                #children
+            }
+        ));
+    }
+    if let Some(children) = &gtk.otherwise {
+        let children = expand_children(children);
+        out.extend(quote!(
+            else {
+                #children
+            }
+        ));
+    }
+    out
+}
+
+fn expand_match(gtk: &GtkMatch) -> TokenStream {
+    let expr = to_stream(&gtk.expr);
+    let arms = gtk.arms.iter().map(|arm| {
+        let pattern = to_stream(&arm.pattern);
+        let guard = arm.guard.as_ref().map(|guard| {
+            let guard = to_stream(guard);
+            quote!(if #guard)
+        });
+        let children = expand_children(&arm.body);
+        quote!(
+            #pattern #guard => {
+                #children
+            }
+        )
+    });
+    quote!(
+        match #expr {
+            #(#arms)*
+        }
+    )
+}
+
+fn expand_for(gtk: &GtkFor) -> TokenStream {
+    let pattern = to_stream(&gtk.pattern);
+    let iter = to_stream(&gtk.iter);
+    let children = match &gtk.key {
+        None => expand_children(&gtk.body),
+        Some(key) => match with_key(&gtk.body, key) {
+            Ok(child) => expand_children(std::iter::once(&child)),
+            Err(error) => return error,
+        },
+    };
+    quote!(
+        for #pattern in #iter {
+            #children
+        }
+    )
+}
+
+/// Give the body of a keyed `for` element its key, by adding it as a `key`
+/// attribute to its one and only child.
+fn with_key(body: &[GtkElement], key: &Tokens) -> Result<GtkElement, TokenStream> {
+    let span = key[0].span();
+    let attributes = match body {
+        [GtkElement::Widget(GtkWidget { attributes, .. })]
+        | [GtkElement::Component(GtkComponent { attributes, .. })] => attributes,
+        _ => {
+            return Err(quote_spanned! { span =>
+                compile_error! { "the body of a keyed `for` must be a single widget or component" }
+            })
+        }
+    };
+    if let Some(attribute) = attributes.iter().find(|attribute| is_key(attribute)) {
+        let span = match attribute {
+            Attribute::Property { name, .. } => name.span(),
+            _ => span,
+        };
+        return Err(quote_spanned! { span =>
+            compile_error! { "this element already gets its key from the `for` around it" }
+        });
+    }
+    let key = Attribute::Property {
+        child: false,
+        parent: Tokens::new(),
+        name: Ident::new("key", span),
+        value: key.clone(),
+        qualifier: None,
+        optional: false,
+    };
+    let mut child = body[0].clone();
+    match &mut child {
+        GtkElement::Widget(GtkWidget { attributes, .. })
+        | GtkElement::Component(GtkComponent { attributes, .. }) => attributes.push(key),
+        _ => unreachable!(),
+    }
+    Ok(child)
+}
+
+/// If `child` is a named slot for `component`, like `<@Card::header>` inside
+/// `<@Card>`, return the slot's name and contents.
+fn as_slot<'a>(
+    component: &GtkComponent,
+    child: &'a GtkElement,
+) -> Option<(&'a Ident, &'a GtkComponent)> {
+    let slot = match child {
+        GtkElement::Component(slot) => slot,
+        _ => return None,
+    };
+    let parent_name = component.name.iter().map(ToString::to_string);
+    match slot.name.split_last() {
+        Some((Token::Ident(name), path))
+            if path.len() == component.name.len() + 1
+                && path[..component.name.len()]
+                    .iter()
+                    .map(ToString::to_string)
+                    .eq(parent_name)
+                && path[component.name.len()].to_string() == "::" =>
+        {
+            Some((name, slot))
+        }
+        _ => None,
     }
 }
 
+fn expand_slots(gtk: &GtkComponent) -> TokenStream {
+    let mut out = TokenStream::new();
+    let mut children = Vec::new();
+    for child in &gtk.children {
+        match as_slot(gtk, child) {
+            Some((name, slot)) => {
+                if let Some(attribute) = slot.attributes.first() {
+                    let span = match attribute {
+                        Attribute::Property { name, .. }
+                        | Attribute::Handler { name, .. }
+                        | Attribute::Binding { name, .. } => name.span(),
+                        Attribute::NamedHandler { name, .. } => name.span(),
+                        Attribute::Spread { value } => value[0].span(),
+                    };
+                    return quote_spanned! {span =>
+                        compile_error! { "slots cannot have attributes" }
+                    };
+                }
+                let nodes = expand_children(&slot.children);
+                out.extend(quote!(
+                    props.#name = {
+                        let mut children = std::vec::Vec::new();
+                        #nodes
+                        vcomp.children(children)
+                    };
+                ));
+            }
+            None => children.push(child),
+        }
+    }
+    if !children.is_empty() {
+        let nodes = expand_children(children);
+        out.extend(quote!(
+            props.children = {
+                let mut children = std::vec::Vec::new();
+                #nodes
+                vcomp.children(children)
+            };
+        ));
+    }
+    out
+}
+
 pub fn expand_component(gtk: &GtkComponent) -> TokenStream {
     let name = to_stream(&gtk.name);
     let mut out = quote!(
@@ -42,19 +363,58 @@ pub fn expand_component(gtk: &GtkComponent) -> TokenStream {
         let mut vcomp = VComponent::new::<#name>();
         let mut props = <#name as Component>::Properties::default();
     );
+    match find_spread(&gtk.attributes) {
+        Ok(Some(value)) => {
+            let value = to_stream(value);
+            out.extend(quote!(
+                props = PropTransform::transform(&vcomp, #value);
+            ));
+        }
+        Ok(None) => {}
+        Err(error) => return error,
+    }
     for attribute in &gtk.attributes {
         out.extend(match attribute {
+            Attribute::Property { name, .. }
+                if is_optional(attribute) && (is_key(attribute) || is_ref(attribute)) =>
+            {
+                let span = name.span();
+                let error_msg = format!("`{}` cannot be optional", name);
+                return quote_spanned! {span =>
+                    compile_error! { #error_msg }
+                };
+            }
+            Attribute::Property { value, .. } if is_key(attribute) => {
+                let key = expand_key(value);
+                quote!(
+                    vcomp.key = #key;
+                )
+            }
+            Attribute::Property { value, .. } if is_ref(attribute) => {
+                let value = to_stream(value);
+                quote!(
+                    vcomp.reference = Some(vgtk::vnode::VRef::any(&(#value)));
+                )
+            }
             Attribute::Property {
                 child,
                 parent,
                 name,
                 value,
+                qualifier,
+                optional,
             } => {
                 if *child {
-                    let prop = expand_property(None, *child, parent, name, value);
-                    quote!(
-                        vcomp.child_props.push(#prop);
-                    )
+                    let expand =
+                        |value: &[Token]| expand_property(None, *child, parent, name, value, *qualifier);
+                    if *optional {
+                        expand_optional_property(quote!(vcomp.child_props), true, name, value, expand)
+                    } else {
+                        let prop = expand(value);
+                        quote!(
+                            vcomp.child_props.push(#prop);
+                        )
+                    }
                 } else {
                     if !parent.is_empty() {
                         let span = parent[0].span();
@@ -62,10 +422,25 @@ pub fn expand_component(gtk: &GtkComponent) -> TokenStream {
                             compile_error! { "component properties cannot have paths" }
                         };
                     }
-                    let value = to_stream(value);
-                    quote!(
-                        props.#name = PropTransform::transform(&vcomp, #value);
-                    )
+                    if let Some(qualifier) = qualifier {
+                        let span = name.span();
+                        let error_msg =
+                            format!("component properties cannot be `{}:`", qualifier.as_str());
+                        return quote_spanned! {span =>
+                            compile_error! { #error_msg }
+                        };
+                    }
+                    let expand = |value: &[Token]| {
+                        let value = to_stream(value);
+                        quote!(
+                            props.#name = PropTransform::transform(&vcomp, #value);
+                        )
+                    };
+                    if *optional {
+                        expand_optional(value, expand)
+                    } else {
+                        expand(value)
+                    }
                 }
             }
             Attribute::Handler {
@@ -79,6 +454,9 @@ pub fn expand_component(gtk: &GtkComponent) -> TokenStream {
                         compile_error! { "component callbacks cannot be async" }
                     };
                 }
+                if let Err(error) = check_handler_args(args) {
+                    return error;
+                }
                 let name = Ident::new(&format!("on_{}", name.to_string()), name.span());
                 let args = to_stream(args);
                 let body = to_stream(body);
@@ -86,8 +464,22 @@ pub fn expand_component(gtk: &GtkComponent) -> TokenStream {
                     props.#name = PropTransform::transform(&vcomp, move #args #body);
                 )
             }
+            Attribute::NamedHandler { name, .. } => {
+                let span = name.span();
+                return quote_spanned! {span =>
+                    compile_error! { "components don't have signals to connect by name" }
+                };
+            }
+            Attribute::Binding { name, .. } => {
+                let span = name.span();
+                return quote_spanned! {span =>
+                    compile_error! { "component properties cannot be `bind:` - pass a value and a `Callback` instead" }
+                };
+            }
+            Attribute::Spread { .. } => continue,
         })
     }
+    out.extend(expand_slots(gtk));
     quote!({
         #out
         vcomp.set_props::<#name>(props);
@@ -95,13 +487,6 @@ pub fn expand_component(gtk: &GtkComponent) -> TokenStream {
     })
 }
 
-fn is_block(gtk: &GtkElement) -> Option<&Group> {
-    match gtk {
-        GtkElement::Block(block) => Some(block),
-        _ => None,
-    }
-}
-
 pub fn expand_widget(gtk: &GtkWidget) -> TokenStream {
     let name = to_stream(&gtk.name);
     let (prop_count, child_prop_count, handler_count) = count_attributes(&gtk.attributes);
@@ -111,6 +496,8 @@ pub fn expand_widget(gtk: &GtkWidget) -> TokenStream {
         use vgtk::lib::glib::StaticType;
         use std::vec::Vec;
         let object_type = #name::static_type();
+        let mut key = None;
+        let mut reference = None;
         let mut properties = Vec::with_capacity(#prop_count);
         let mut child_props = Vec::with_capacity(#child_prop_count);
         let mut handlers = Vec::with_capacity(#handler_count);
@@ -128,22 +515,95 @@ pub fn expand_widget(gtk: &GtkWidget) -> TokenStream {
             let constructor = None;
         ));
     }
+    let spread = match find_spread(&gtk.attributes) {
+        Ok(spread) => spread,
+        Err(error) => return error,
+    };
+    let mut classes = TokenStream::new();
     for attribute in &gtk.attributes {
         out.extend(match attribute {
+            Attribute::Property { name, .. }
+                if is_optional(attribute)
+                    && (is_key(attribute)
+                        || is_ref(attribute)
+                        || is_class(attribute)
+                        || is_style(attribute)) =>
+            {
+                let span = name.span();
+                let error_msg = format!("`{}` cannot be optional", name);
+                return quote_spanned! {span =>
+                    compile_error! { #error_msg }
+                };
+            }
+            Attribute::Property { value, .. } if is_key(attribute) => {
+                let key = expand_key(value);
+                quote!(
+                    key = #key;
+                )
+            }
+            Attribute::Property { value, .. } if is_ref(attribute) => {
+                let value = to_stream(value);
+                quote!(
+                    reference = Some(vgtk::vnode::VRef::new::<#name, _>(&(#value)));
+                )
+            }
+            Attribute::Property { value, .. } if is_class(attribute) => {
+                let value = to_stream(value);
+                classes.extend(quote!(
+                    vgtk::properties::add_classes(&mut classes, &(#value));
+                ));
+                continue;
+            }
+            Attribute::Property { value, .. } if is_style(attribute) => {
+                let value = to_stream(value);
+                quote!(
+                    properties.push(vgtk::properties::style_property(&(#value)));
+                )
+            }
+            Attribute::Property {
+                parent,
+                name,
+                value,
+                qualifier: Some(Qualifier::Class),
+                ..
+            } => {
+                if !parent.is_empty() {
+                    let span = parent[0].span();
+                    return quote_spanned! {span =>
+                        compile_error! { "style classes cannot have paths" }
+                    };
+                }
+                let class = to_string_literal(name.to_string().replace('_', "-"));
+                let value = to_stream(value);
+                classes.extend(quote!(
+                    if #value {
+                        classes.push(std::string::String::from(#class));
+                    }
+                ));
+                continue;
+            }
             Attribute::Property {
                 child,
                 parent,
                 name,
                 value,
+                qualifier,
+                optional,
             } => {
-                let prop = expand_property(Some(&gtk.name), *child, &parent, &name, &value);
-                if *child {
-                    quote!(
-                        child_props.push(#prop);
-                    )
+                let expand = |value: &[Token]| {
+                    expand_property(Some(&gtk.name), *child, parent, name, value, *qualifier)
+                };
+                let list = if *child {
+                    quote!(child_props)
                 } else {
+                    quote!(properties)
+                };
+                if *optional {
+                    expand_optional_property(list, *child, name, value, expand)
+                } else {
+                    let prop = expand(value);
                     quote!(
-                        properties.push(#prop);
+                        #list.push(#prop);
                     )
                 }
             }
@@ -152,25 +612,53 @@ pub fn expand_widget(gtk: &GtkWidget) -> TokenStream {
                 async_keyword,
                 args,
                 body,
-            } => expand_handler(&gtk.name, &name, async_keyword.as_ref(), &args, &body),
+            } => {
+                if let Err(error) = check_handler_args(args) {
+                    return error;
+                }
+                expand_handler(&gtk.name, &name, async_keyword.as_ref(), &args, &body)
+            }
+            Attribute::NamedHandler {
+                name,
+                async_keyword,
+                args,
+                body,
+            } => {
+                if let Err(error) = check_handler_args(args) {
+                    return error;
+                }
+                expand_named_handler(&name, async_keyword.as_ref(), &args, &body)
+            }
+            Attribute::Binding {
+                name,
+                value,
+                message,
+            } => expand_binding(&gtk.name, name, value, message),
+            Attribute::Spread { .. } => continue,
         });
     }
-    for child in &gtk.children {
-        if let Some(block) = is_block(child) {
-            out.extend(quote!(
-                children.extend(#block);
-            ));
-        } else {
-            let child = expand_gtk(child);
-            out.extend(quote!(
-                children.push(#child);
-            ));
-        }
+    if !classes.is_empty() {
+        out.extend(quote!(
+            properties.push({
+                let mut classes = Vec::new();
+                #classes
+                vgtk::properties::class_property(classes)
+            });
+        ));
     }
+    if let Some(value) = spread {
+        let value = to_stream(value);
+        out.extend(quote!(
+            vgtk::Attributes::spread(&(#value), &mut properties);
+        ));
+    }
+    out.extend(expand_children(&gtk.children));
     quote!({
         #out
         VNode::Object(VObject {
             object_type,
+            key,
+            reference,
             constructor,
             properties,
             child_props,
@@ -186,7 +674,17 @@ pub fn expand_property(
     parent: &[Token],
     name: &Ident,
     value: &[Token],
+    qualifier: Option<Qualifier>,
 ) -> TokenStream {
+    match qualifier {
+        Some(Qualifier::Prop) => {
+            return expand_property_by_name(child_prop, parent, name, value, false)
+        }
+        Some(Qualifier::Construct) => {
+            return expand_property_by_name(child_prop, parent, name, value, true)
+        }
+        _ => {}
+    }
     let child_prefix = if child_prop { "child_" } else { "" };
     let mut parent_type: Vec<Token> = parent.to_vec();
     while let Some(Token::Punct2(_, _, _, _)) = parent_type.last() {
@@ -214,16 +712,37 @@ pub fn expand_property(
     } else {
         quote!()
     };
+    // Live properties are compared against what the getter says, because the
+    // user may have changed them since we last set them. Everything else only
+    // needs to be set when it differs from what the previous render set.
+    let getter_call = if child_prop {
+        quote!(parent.#getter(object))
+    } else if parent_type.is_empty() {
+        quote!(object.#getter())
+    } else {
+        quote!(#parent_type::#getter(object))
+    };
+    let changed = if qualifier == Some(Qualifier::Live) || qualifier == Some(Qualifier::Bind) {
+        quote!(previous.is_none() || !value.compare(#getter_call))
+    } else {
+        quote!(value.changed(previous, || #getter_call))
+    };
     let setter_body = if !child_prop {
-        if parent_type.is_empty() {
+        if qualifier == Some(Qualifier::Bind) {
+            quote!(
+                if #changed {
+                    vgtk::properties::set_bound(object, || object.#setter(value.coerce()));
+                }
+            )
+        } else if parent_type.is_empty() {
             quote!(
-                if force || !value.compare(object.#getter()) {
+                if #changed {
                     object.#setter(value.coerce());
                 }
             )
         } else {
             quote!(
-                if force || !value.compare(#parent_type::#getter(object)) {
+                if #changed {
                     #parent_type::#setter(object, value.coerce());
                 }
             )
@@ -232,7 +751,7 @@ pub fn expand_property(
         quote!(
             let parent: &#parent_type = parent.expect("child attribute without a reachable parent").downcast_ref()
                   .unwrap_or_else(|| panic!("downcast to {:?} failed on parent in property setter", #parent_type::static_type()));
-            if force || !value.compare(parent.#getter(object)) {
+            if #changed {
                 parent.#setter(object, value.coerce());
             }
         )
@@ -247,10 +766,78 @@ pub fn expand_property(
             let value = #value;
             VProperty {
                 name: #prop_name,
-                set: std::boxed::Box::new(move |object: &vgtk::lib::glib::Object, parent: Option<&vgtk::lib::glib::Object>, force: bool| {
+                value: value.value(),
+                set: std::rc::Rc::new(move |object: &vgtk::lib::glib::Object, parent: Option<&vgtk::lib::glib::Object>, previous: Option<&dyn std::any::Any>| {
                     #setter_prelude
                     #setter_body
                 }),
+                construct: None,
+                unset: None,
+            }
+        }
+    )
+}
+
+/// Expand a `prop:` or `construct:` property, which is set through its GObject
+/// name instead of a setter method, so its type is only checked at runtime.
+fn expand_property_by_name(
+    child_prop: bool,
+    parent: &[Token],
+    name: &Ident,
+    value: &[Token],
+    rebuild: bool,
+) -> TokenStream {
+    if child_prop && rebuild {
+        let span = name.span();
+        return quote_spanned! {span =>
+            compile_error! { "child properties cannot be `construct:`" }
+        };
+    }
+    let mut parent_type: Vec<Token> = parent.to_vec();
+    while let Some(Token::Punct2(_, _, _, _)) = parent_type.last() {
+        parent_type.pop();
+    }
+    if !child_prop && !parent_type.is_empty() {
+        let span = parent[0].span();
+        return quote_spanned! {span =>
+            compile_error! { "properties set by name cannot have paths" }
+        };
+    }
+    let parent_type = to_stream(parent_type.iter());
+    let value_span = value[0].span();
+    let value = to_stream(value);
+    let value = quote_spanned!(value_span => std::rc::Rc::new(#value));
+    let prop_name = to_string_literal(name);
+    let gobject_name = to_string_literal(name.to_string().replace('_', "-"));
+    let (setter, construct) = if child_prop {
+        (
+            quote!(vgtk::properties::set_child_property_by_name::<#parent_type, _>(
+                object, parent, #gobject_name, &*value, previous
+            )),
+            quote!(None),
+        )
+    } else {
+        (
+            quote!(vgtk::properties::set_property_by_name(object, #gobject_name, &*value, previous)),
+            if rebuild {
+                quote!(Some(vgtk::properties::rebuild_by_name(#gobject_name, value.clone())))
+            } else {
+                quote!(Some(vgtk::properties::construct_by_name(#gobject_name, value.clone())))
+            },
+        )
+    };
+    quote!(
+        {
+            let value = #value;
+            let construct = #construct;
+            VProperty {
+                name: #prop_name,
+                value: value.clone(),
+                set: std::rc::Rc::new(move |object: &vgtk::lib::glib::Object, parent: Option<&vgtk::lib::glib::Object>, previous: Option<&dyn std::any::Any>| {
+                    #setter
+                }),
+                construct,
+                unset: None,
             }
         }
     )
@@ -265,38 +852,303 @@ pub fn expand_handler(
 ) -> TokenStream {
     let object_type = to_stream(object_type);
     let args_s = to_stream(args);
-    let body_s = to_stream(body);
     let connect = Ident::new(&format!("connect_{}", name.to_string()), name.span());
     let signal_name = to_string_literal(name);
     let location = args.first().expect("signal handler is empty!").span();
     let signal_id = to_string_literal(format!("{:?}", location));
-    let inner_block = if async_keyword.is_some() {
+    let inner_block = expand_handler_body(async_keyword, body);
+    let trampoline_args: Vec<_> = (0..count_closure_args(args))
+        .map(|index| Ident::new(&format!("__arg{}", index), Span::call_site()))
+        .collect();
+    quote!(
+        handlers.push(VHandler::new(
+            #signal_name,
+            #signal_id,
+            move |scope: &Scope<_>| {
+                let scope: Scope<_> = scope.clone();
+                vgtk::vnode::signal_handler(<#object_type>::#connect, move #args_s #inner_block)
+            },
+            |object: &vgtk::lib::glib::Object, slot, handler_type| {
+                use vgtk::lib::glib::object::Cast;
+                let object: &#object_type = object.downcast_ref()
+                      .unwrap_or_else(|| panic!("downcast to {:?} failed in signal setter", #object_type::static_type()));
+                object.#connect(move |#(#trampoline_args),*| {
+                    let handler = slot.get(handler_type);
+                    handler(#(#trampoline_args),*)
+                })
+            }
+        ));
+    )
+}
+
+pub fn expand_named_handler(
+    name: &Literal,
+    async_keyword: Option<&Token>,
+    args: &[Token],
+    body: &[Token],
+) -> TokenStream {
+    let args_s = to_stream(args);
+    let location = args.first().expect("signal handler is empty!").span();
+    let signal_id = to_string_literal(format!("{:?}", location));
+    let inner_block = expand_handler_body(async_keyword, body);
+    quote!(
+        handlers.push(VHandler::new(
+            #name,
+            #signal_id,
+            move |scope: &Scope<_>| {
+                let scope: Scope<_> = scope.clone();
+                vgtk::vnode::event_handler(move #args_s #inner_block)
+            },
+            |object: &vgtk::lib::glib::Object, slot, handler_type| {
+                vgtk::vnode::connect_event(object, #name, slot, handler_type)
+            }
+        ));
+    )
+}
+
+/// Expand a `bind:` attribute into a live property and a handler which sends
+/// the property's new value to the message constructor whenever the user
+/// changes it.
+fn expand_binding(
+    object_type: &[Token],
+    name: &Ident,
+    value: &[Token],
+    message: &[Token],
+) -> TokenStream {
+    let prop = expand_property(
+        Some(object_type),
+        false,
+        &[],
+        name,
+        value,
+        Some(Qualifier::Bind),
+    );
+    let object_type = to_stream(object_type);
+    let getter = Ident::new(&format!("get_{}", name), name.span());
+    let handler_name = to_string_literal(format!("bind:{}", name));
+    let gobject_name = to_string_literal(name.to_string().replace('_', "-"));
+    let signal_id = to_string_literal(format!("{:?}", name.span()));
+    let message_span = message[0].span();
+    let message = to_stream(message);
+    let message = quote_spanned!(message_span => (#message));
+    quote!(
+        properties.push(#prop);
+        handlers.push(VHandler::new(
+            #handler_name,
+            #signal_id,
+            move |scope: &Scope<_>| {
+                let scope: Scope<_> = scope.clone();
+                vgtk::vnode::event_handler(move |event: vgtk::Event| {
+                    if !vgtk::properties::is_setting_bound(&event.source) {
+                        use vgtk::lib::glib::object::Cast;
+                        let object: &#object_type = event.source.downcast_ref()
+                            .unwrap_or_else(|| panic!("downcast to {:?} failed in binding handler", #object_type::static_type()));
+                        let value = object.#getter();
+                        vgtk::vnode::handler_result::<_, (), _>(&scope, #message(std::convert::Into::into(value)));
+                    }
+                    None
+                })
+            },
+            |object: &vgtk::lib::glib::Object, slot, handler_type| {
+                let signal = vgtk::properties::bound_signal(object, #gobject_name);
+                vgtk::vnode::connect_event(object, &signal, slot, handler_type)
+            }
+        ));
+    )
+}
+
+/// The body of a signal handler closure, which sends the message from the
+/// handler's result and returns the rest to GTK.
+fn expand_handler_body(async_keyword: Option<&Token>, body: &[Token]) -> TokenStream {
+    let body = to_stream(body);
+    if async_keyword.is_some() {
         quote!({
             let scope = scope.clone();
             vgtk::lib::glib::MainContext::ref_thread_default().spawn_local(
                 async move {
-                    let msg = async move { #body_s }.await;
-                    scope.send_message(msg);
+                    let result = async move { #body }.await;
+                    vgtk::vnode::handler_result::<_, (), _>(&scope, result);
                 }
-            )
+            );
+            std::default::Default::default()
         })
     } else {
         quote!({
-            let msg = { #body_s };
-            scope.send_message(msg);
+            vgtk::vnode::handler_result(&scope, { #body })
         })
-    };
-    quote!(
-        handlers.push(VHandler {
-            name: #signal_name,
-            id: #signal_id,
-            set: std::boxed::Box::new(move |object: &vgtk::lib::glib::Object, scope: &Scope<_>| {
-                use vgtk::lib::glib::object::Cast;
-                let object: &#object_type = object.downcast_ref()
-                      .unwrap_or_else(|| panic!("downcast to {:?} failed in signal setter", #object_type::static_type()));
-                let scope: Scope<_> = scope.clone();
-                object.#connect(move #args_s #inner_block)
-            })
+    }
+}
+
+/// Refuse a handler closure which takes no arguments, as in `||`, as signals
+/// and callbacks always call their handlers with some.
+fn check_handler_args(args: &[Token]) -> Result<(), TokenStream> {
+    if count_closure_args(args) == 0 {
+        let span = args[0].span();
+        return Err(quote_spanned! {span =>
+            compile_error! { "a handler has to take the arguments it's called with - use `|_|` to ignore them" }
         });
-    )
+    }
+    Ok(())
+}
+
+/// Count the arguments in a closure's argument list, which may be an empty
+/// `||`, skipping over any commas inside type parameters.
+fn count_closure_args(args: &[Token]) -> usize {
+    let mut depth = 0;
+    let mut count = 0;
+    let mut in_arg = false;
+    for token in args {
+        match token {
+            Token::Punct1('|', _) | Token::Punct2('|', '|', _, _) if depth == 0 => {}
+            Token::Punct1(',', _) if depth == 0 => in_arg = false,
+            token => {
+                match token {
+                    Token::Punct1('<', _) => depth += 1,
+                    Token::Punct1('>', _) => depth -= 1,
+                    Token::Punct2('>', '>', _, _) => depth -= 2,
+                    _ => {}
+                }
+                if !in_arg {
+                    count += 1;
+                    in_arg = true;
+                }
+            }
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_closure_args, expand_gtk};
+    use crate::lexer::Tokens;
+    use crate::parser::grammar::GtkElementParser;
+
+    fn tokens(source: &str) -> Tokens {
+        source.parse::<proc_macro2::TokenStream>().unwrap().into()
+    }
+
+    fn expand(source: &str) -> String {
+        let tokens = tokens(source);
+        let element = GtkElementParser::new()
+            .parse(tokens.lexer())
+            .unwrap_or_else(|error| panic!("failed to parse {:?}: {:?}", source, error));
+        expand_gtk(&element).to_string()
+    }
+
+    #[test]
+    fn counts_closure_args() {
+        assert_eq!(count_closure_args(&tokens("||")), 0);
+        assert_eq!(count_closure_args(&tokens("|_|")), 1);
+        assert_eq!(count_closure_args(&tokens("|button, _|")), 2);
+        assert_eq!(count_closure_args(&tokens("|a: &Button, b: u32, c|")), 3);
+    }
+
+    #[test]
+    fn skips_commas_in_type_parameters() {
+        assert_eq!(
+            count_closure_args(&tokens("|map: HashMap<String, u32>|")),
+            1
+        );
+        assert_eq!(
+            count_closure_args(&tokens("|a: Vec<HashMap<u8, u8>>, b: Result<(), ()>|")),
+            2
+        );
+    }
+
+    #[test]
+    fn rejects_handlers_without_arguments() {
+        for source in &[
+            "<Button on clicked=|| Message::Clicked />",
+            "<Entry on \"activate\"=|| Message::Activated />",
+            "<@MyButton on clicked=|| Message::Clicked />",
+        ] {
+            let expanded = expand(source);
+            assert!(
+                expanded.contains("a handler has to take the arguments it's called with"),
+                "{}",
+                expanded
+            );
+        }
+        let expanded = expand("<Button on clicked=|_| Message::Clicked />");
+        assert!(expanded.contains("handler (__arg0)"), "{}", expanded);
+    }
+
+    #[test]
+    fn rejects_keyed_for_bodies_without_one_element() {
+        let expanded =
+            expand("<ListBox> for item in items; key = item.id { <Label /> <Label /> } </ListBox>");
+        assert!(
+            expanded.contains("the body of a keyed `for` must be a single widget or component"),
+            "{}",
+            expanded
+        );
+        let expanded = expand(
+            "<ListBox> for item in items; key = item.id { <Label key=item.id /> } </ListBox>",
+        );
+        assert!(
+            expanded.contains("this element already gets its key from the `for` around it"),
+            "{}",
+            expanded
+        );
+    }
+
+    #[test]
+    fn rejects_bindings_on_components() {
+        let expanded = expand("<@Form bind:name=self.name.clone() => Message::Name />");
+        assert!(
+            expanded.contains("component properties cannot be `bind:`"),
+            "{}",
+            expanded
+        );
+    }
+
+    #[test]
+    fn rejects_style_classes_with_paths() {
+        let expanded = expand("<Label class:Box::warning=self.warning />");
+        assert!(
+            expanded.contains("style classes cannot have paths"),
+            "{}",
+            expanded
+        );
+    }
+
+    #[test]
+    fn resets_optional_properties_when_they_go_away() {
+        let expanded = expand("<Label tooltip_text?=self.tooltip.clone() />");
+        assert!(
+            expanded.contains("if let Some (optional_value)"),
+            "{}",
+            expanded
+        );
+        assert!(
+            expanded.contains("reset_property_by_name (object , \"tooltip-text\")"),
+            "{}",
+            expanded
+        );
+        let expanded = expand("<Box><Label Box::padding?=self.padding /></Box>");
+        assert!(
+            expanded.contains("reset_child_property_by_name (object , parent , \"padding\")"),
+            "{}",
+            expanded
+        );
+    }
+
+    #[test]
+    fn rejects_misplaced_optional_and_spread_attributes() {
+        let expanded = expand("<Button ..self.common ..self.extra />");
+        assert!(
+            expanded.contains("an element can only have one `..` attribute"),
+            "{}",
+            expanded
+        );
+        for source in &["<Label key?=self.id />", "<@Form key?=self.id />"] {
+            let expanded = expand(source);
+            assert!(
+                expanded.contains("`key` cannot be optional"),
+                "{}",
+                expanded
+            );
+        }
+    }
 }
@@ -12,6 +12,11 @@ pub type Spanned<Tok, Loc, Error> = Result<(Loc, Tok, Loc), Error>;
 pub enum Keyword {
     Async,
     On,
+    If,
+    Else,
+    Match,
+    For,
+    In,
 }
 
 fn keywordise(token: Token) -> Token {
@@ -19,12 +24,46 @@ fn keywordise(token: Token) -> Token {
         Token::Ident(ident) => match ident.to_string().as_str() {
             "async" => Token::Keyword(Keyword::Async, ident),
             "on" => Token::Keyword(Keyword::On, ident),
+            "if" => Token::Keyword(Keyword::If, ident),
+            "else" => Token::Keyword(Keyword::Else, ident),
+            "match" => Token::Keyword(Keyword::Match, ident),
+            "for" => Token::Keyword(Keyword::For, ident),
+            "in" => Token::Keyword(Keyword::In, ident),
             _ => Token::Ident(ident),
         },
         _ => token,
     }
 }
 
+/// Test whether a run of joint punctuation characters makes up one of the
+/// operators the grammar knows about. Any other run is left as separate
+/// characters, so that eg. the `=|` in `on clicked=|_| ...` isn't read as a
+/// single unknown operator.
+fn is_operator(chars: &[char]) -> bool {
+    matches!(
+        chars,
+        ['=', '=']
+            | ['!', '=']
+            | ['<', '=']
+            | ['>', '=']
+            | ['<', '<']
+            | ['>', '>']
+            | ['&', '&']
+            | ['|', '|']
+            | ['-', '>']
+            | [':', ':']
+            | ['=', '>']
+            | ['.', '.']
+            | ['<', '/']
+            | ['/', '>']
+            | ['<', '@']
+            | ['<', '>']
+            | ['<', '/', '>']
+            | ['<', '/', '@']
+            | ['.', '.', '=']
+    )
+}
+
 #[derive(Clone, Debug)]
 pub enum Token {
     Ident(Ident),
@@ -325,30 +364,24 @@ impl From<TokenStream> for Tokens {
                 TokenTree::Punct(punct) => {
                     if let Some(prev) = vec.pop() {
                         let next = match prev {
-                            Token::Punct1(prev_chr, prev_punct) => {
-                                if prev_punct.spacing() == Spacing::Joint {
-                                    Token::Punct2(prev_chr, punct.as_char(), prev_punct, punct)
-                                } else {
-                                    vec.push(Token::Punct1(prev_chr, prev_punct));
-                                    punct.into()
-                                }
+                            Token::Punct1(prev_chr, prev_punct)
+                                if prev_punct.spacing() == Spacing::Joint
+                                    && is_operator(&[prev_chr, punct.as_char()]) =>
+                            {
+                                Token::Punct2(prev_chr, punct.as_char(), prev_punct, punct)
                             }
-                            Token::Punct2(prev_chr_1, prev_chr_2, prev_p1, prev_p2) => {
-                                if prev_p2.spacing() == Spacing::Joint {
-                                    Token::Punct3(
-                                        prev_chr_1,
-                                        prev_chr_2,
-                                        punct.as_char(),
-                                        prev_p1,
-                                        prev_p2,
-                                        punct,
-                                    )
-                                } else {
-                                    vec.push(Token::Punct2(
-                                        prev_chr_1, prev_chr_2, prev_p1, prev_p2,
-                                    ));
-                                    punct.into()
-                                }
+                            Token::Punct2(prev_chr_1, prev_chr_2, prev_p1, prev_p2)
+                                if prev_p2.spacing() == Spacing::Joint
+                                    && is_operator(&[prev_chr_1, prev_chr_2, punct.as_char()]) =>
+                            {
+                                Token::Punct3(
+                                    prev_chr_1,
+                                    prev_chr_2,
+                                    punct.as_char(),
+                                    prev_p1,
+                                    prev_p2,
+                                    punct,
+                                )
                             }
                             prev => {
                                 vec.push(prev);
@@ -2,5 +2,290 @@
 #![allow(clippy::all)]
 
 use lalrpop_util::lalrpop_mod;
+use proc_macro2::Group;
+
+use crate::context::{GtkElement, GtkMatchArm};
+use crate::error::{ParseError, RsxParseError};
+use crate::lexer::Tokens;
 
 lalrpop_mod!(pub grammar);
+
+/// Parse the contents of a block as a list of child elements, as in the body of
+/// an `if` or a `for`.
+pub fn parse_children(group: Group) -> Result<Vec<GtkElement>, ParseError> {
+    let tokens: Tokens = group.stream().into();
+    grammar::GtkChildrenParser::new()
+        .parse(tokens.lexer())
+        .map_err(|error| nested(group, tokens, error))
+}
+
+/// Parse the contents of a `match` element's block as a list of match arms.
+pub fn parse_match_arms(group: Group) -> Result<Vec<GtkMatchArm>, ParseError> {
+    let tokens: Tokens = group.stream().into();
+    grammar::MatchArmsParser::new()
+        .parse(tokens.lexer())
+        .map_err(|error| nested(group, tokens, error))
+}
+
+fn nested(group: Group, tokens: Tokens, error: ParseError) -> ParseError {
+    ParseError::User {
+        error: RsxParseError::Nested {
+            group,
+            tokens,
+            error: Box::new(error),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::grammar::GtkElementParser;
+    use super::ParseError;
+    use crate::context::{Attribute, GtkElement, GtkIf, GtkMatch, Qualifier};
+    use crate::error::RsxParseError;
+    use crate::lexer::Tokens;
+
+    fn parse(source: &str) -> GtkElement {
+        let tokens: Tokens = source.parse::<proc_macro2::TokenStream>().unwrap().into();
+        GtkElementParser::new()
+            .parse(tokens.lexer())
+            .unwrap_or_else(|error| panic!("failed to parse {:?}: {:?}", source, error))
+    }
+
+    fn attributes(element: &GtkElement) -> &[Attribute] {
+        match element {
+            GtkElement::Widget(widget) => &widget.attributes,
+            GtkElement::Component(component) => &component.attributes,
+            element => panic!("expected a widget or a component, got {:?}", element),
+        }
+    }
+
+    fn parse_error(source: &str) -> RsxParseError {
+        let tokens: Tokens = source.parse::<proc_macro2::TokenStream>().unwrap().into();
+        match GtkElementParser::new().parse(tokens.lexer()) {
+            Err(ParseError::User { error }) => error,
+            Err(error) => panic!("expected a vgtk error from {:?}, got {:?}", source, error),
+            Ok(element) => panic!("expected {:?} not to parse, got {:?}", source, element),
+        }
+    }
+
+    fn children(element: &GtkElement) -> &[GtkElement] {
+        match element {
+            GtkElement::Widget(widget) => &widget.children,
+            GtkElement::Component(component) => &component.children,
+            GtkElement::Fragment(children) => children,
+            element => panic!("expected an element with children, got {:?}", element),
+        }
+    }
+
+    #[test]
+    fn parses_handlers() {
+        for source in &[
+            "<Button on clicked=|_| Message::Clicked />",
+            "<Button on clicked=|| Message::Clicked />",
+            "<Button on clicked=async |_| Message::Clicked />",
+            "<Entry on \"activate\"=|event| Message::Activated(event.object()) />",
+        ] {
+            match attributes(&parse(source)) {
+                [Attribute::Handler { .. }] | [Attribute::NamedHandler { .. }] => {}
+                other => panic!("expected a handler from {:?}, got {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_fragments() {
+        // Newer compilers hand us `<>` and `</>` joined together, older ones
+        // as separate tokens.
+        for source in &["<><Label /><Label /></>", "< > <Label /> <Label /> < / >"] {
+            match parse(source) {
+                GtkElement::Fragment(children) => assert_eq!(children.len(), 2),
+                other => panic!("expected a fragment from {:?}, got {:?}", source, other),
+            }
+        }
+        let parent = parse("<Box><Label /><><Label /><Label /></></Box>");
+        match children(&parent) {
+            [GtkElement::Widget(_), GtkElement::Fragment(nested)] => assert_eq!(nested.len(), 2),
+            other => panic!("expected a widget and a fragment, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_tags() {
+        assert!(matches!(
+            parse_error("<Box><Label /></Grid>"),
+            RsxParseError::TagMismatch {
+                component: false,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_error("<@Card></@Cord>"),
+            RsxParseError::TagMismatch {
+                component: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_if_elements() {
+        let element = parse(
+            "<Box>
+                if self.loading { <Spinner /> }
+                else if let Some(error) = &self.error { <Label label=error.clone() /> }
+                else { <Label /> <Label /> }
+            </Box>",
+        );
+        match children(&element) {
+            [GtkElement::If(GtkIf {
+                branches,
+                otherwise: Some(otherwise),
+            })] => {
+                assert_eq!(branches.len(), 2);
+                assert_eq!(branches[0].0.to_string(), "self.loading");
+                assert_eq!(branches[1].1.len(), 1);
+                assert_eq!(otherwise.len(), 2);
+            }
+            other => panic!("expected an if, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_match_elements() {
+        let element = parse(
+            "<Box>
+                match &self.state {
+                    State::Empty => <Label label=\"Nothing here\" />,
+                    State::Loading(progress) if *progress < 1.0 => { <Spinner /> <Label /> }
+                    _ => {}
+                }
+            </Box>",
+        );
+        match children(&element) {
+            [GtkElement::Match(GtkMatch { expr, arms })] => {
+                assert_eq!(expr.to_string(), "&self.state");
+                let shapes: Vec<_> = arms
+                    .iter()
+                    .map(|arm| (arm.guard.is_some(), arm.body.len()))
+                    .collect();
+                assert_eq!(shapes, vec![(false, 1), (true, 2), (false, 0)]);
+            }
+            other => panic!("expected a match, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_for_elements() {
+        let element = parse(
+            "<ListBox>
+                for (index, item) in self.items.iter().enumerate(); key = item.id {
+                    <@ItemView item=item.clone() index=index />
+                }
+                for item in &self.extra { <Label /> }
+            </ListBox>",
+        );
+        match children(&element) {
+            [GtkElement::For(keyed), GtkElement::For(unkeyed)] => {
+                assert_eq!(keyed.pattern.to_string(), "(index , item)");
+                assert_eq!(
+                    keyed.key.as_ref().map(ToString::to_string).as_deref(),
+                    Some("item.id")
+                );
+                assert_eq!(keyed.body.len(), 1);
+                assert!(unkeyed.key.is_none());
+            }
+            other => panic!("expected two fors, got {:?}", other),
+        }
+        assert!(matches!(
+            parse_error("for item in items; id = item.id { <Label /> }"),
+            RsxParseError::InvalidKey { .. }
+        ));
+    }
+
+    #[test]
+    fn parses_bindings() {
+        match attributes(&parse(
+            "<Entry bind:text=self.text.clone() => Message::Text />",
+        )) {
+            [Attribute::Binding { name, message, .. }] => {
+                assert_eq!(name.to_string(), "text");
+                assert_eq!(message.to_string(), "Message::Text");
+            }
+            other => panic!("expected a binding, got {:?}", other),
+        }
+        for source in &[
+            "<Entry bind:text=self.text.clone() />",
+            "<Entry text=self.text.clone() => Message::Text />",
+            "<Entry bind:Box::padding=self.padding => Message::Padding />",
+        ] {
+            assert!(
+                matches!(parse_error(source), RsxParseError::InvalidBinding { .. }),
+                "{:?} should be an invalid binding",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn parses_qualifiers() {
+        let element = parse(
+            "<Label live:text=a prop:ellipsize=b construct:name=c class:warning=d Box::padding=e />",
+        );
+        let qualifiers: Vec<_> = attributes(&element)
+            .iter()
+            .map(|attribute| match attribute {
+                Attribute::Property {
+                    qualifier, child, ..
+                } => (*qualifier, *child),
+                other => panic!("expected a property, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            qualifiers,
+            vec![
+                (Some(Qualifier::Live), false),
+                (Some(Qualifier::Prop), false),
+                (Some(Qualifier::Construct), false),
+                (Some(Qualifier::Class), false),
+                (None, true),
+            ]
+        );
+        assert!(matches!(
+            parse_error("<Label fancy:text=a />"),
+            RsxParseError::UnknownQualifier { .. }
+        ));
+    }
+
+    #[test]
+    fn parses_optional_and_spread_attributes() {
+        let element =
+            parse("<Button label=\"Save\" tooltip_text?=self.tooltip.clone() ..self.common />");
+        match attributes(&element) {
+            [Attribute::Property {
+                optional: false, ..
+            }, Attribute::Property {
+                name,
+                optional: true,
+                ..
+            }, Attribute::Spread { value }] => {
+                assert_eq!(name.to_string(), "tooltip_text");
+                assert_eq!(value.to_string(), "self.common");
+            }
+            other => panic!(
+                "expected a property, an optional one and a spread, got {:?}",
+                other
+            ),
+        }
+        for source in &[
+            "<Entry bind:text?=self.text.clone() => Message::Text />",
+            "<Label class:warning?=self.warning />",
+        ] {
+            assert!(
+                matches!(parse_error(source), RsxParseError::InvalidOptional { .. }),
+                "{:?} should be an invalid optional",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,755 @@
+//! How children are added to their parents.
+//!
+//! GTK has many different ways of adding a child to a parent: most containers
+//! take any number of widgets through `Container::add`, but a `Window` puts its
+//! first of two children in its title bar, an `Application` takes windows and
+//! actions, a `MenuItem` takes a single `Menu` as its submenu, and so on.
+//! vgtk looks up how to add, remove and reorder children for a given parent
+//! using the [`ChildAttach`][ChildAttach] implementation registered for its
+//! type, or for the closest of its ancestors which has one.
+//!
+//! Implementations are provided for the GTK types which need them, and you can
+//! [`register()`][register] your own, either for containers which need special
+//! handling or to override the built-in behaviour for a type.
+//!
+//! [ChildAttach]: trait.ChildAttach.html
+//! [register]: fn.register.html
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gio::{Action, ActionExt, ActionMapExt};
+use glib::{prelude::*, Object, Type};
+use gtk::{
+    self, prelude::*, Application, ApplicationWindow, Bin, Box as GtkBox, Container, Dialog,
+    FlowBox, Grid, GridExt, HeaderBar, ListBox, Menu, MenuButton, MenuItem, Notebook,
+    ShortcutsWindow, Widget, Window,
+};
+
+use crate::ffi;
+use crate::vnode::VProperty;
+
+/// The child properties given to a child in the [`gtk!`][gtk!] macro.
+///
+/// Some containers use child properties to decide how to add a child, such as
+/// `Box`'s `center_widget`, which are never actually set on the child.
+///
+/// [gtk!]: ../macro.gtk.html
+#[derive(Clone, Copy)]
+pub struct ChildProps<'a>(&'a [VProperty]);
+
+impl<'a> ChildProps<'a> {
+    pub(crate) fn new(child_props: &'a [VProperty]) -> Self {
+        ChildProps(child_props)
+    }
+
+    /// Test whether the child has a child property by the given name, as in
+    /// `contains("center_widget")` for `<Label Box::center_widget=true />`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|prop| prop.name == name)
+    }
+}
+
+/// A strategy for adding children to a type of parent, and removing and
+/// reordering them again.
+///
+/// Here's how you'd put the two children of a [`Paned`][Paned] in their
+/// respective panes, which `Container::add` would also do, except that the
+/// second child can't be added before the first:
+///
+/// ```rust,no_run
+/// # use vgtk::lib::{glib::{Cast, Object}, gtk::{Paned, PanedExt, Widget}};
+/// use vgtk::attach::{ChildAttach, ChildProps};
+///
+/// struct PanedAttach;
+///
+/// impl ChildAttach for PanedAttach {
+///     type Parent = Paned;
+///
+///     fn add(&self, parent: &Paned, child: &Object, index: usize, _total: usize, _: ChildProps<'_>) {
+///         let widget: &Widget = child.downcast_ref().expect("Paned's children must be Widgets");
+///         match index {
+///             0 => parent.pack1(widget, true, false),
+///             1 => parent.pack2(widget, true, false),
+///             _ => panic!("Paned can only have 2 children"),
+///         }
+///     }
+/// }
+///
+/// vgtk::attach::register(PanedAttach);
+/// ```
+///
+/// [Paned]: ../../gtk/struct.Paned.html
+pub trait ChildAttach {
+    /// The type of parent this strategy applies to. It also applies to any
+    /// subclass of it, unless the subclass has its own.
+    type Parent: IsA<Object>;
+
+    /// Add a child to the parent.
+    ///
+    /// `index` is the child's position among its siblings and `total` is the
+    /// number of siblings, including the child itself. When a parent is first
+    /// built, its children are added in order, but later additions can happen
+    /// at any position.
+    fn add(
+        &self,
+        parent: &Self::Parent,
+        child: &Object,
+        index: usize,
+        total: usize,
+        child_props: ChildProps<'_>,
+    );
+
+    /// Remove a child from the parent.
+    ///
+    /// The default implementation calls `Container::remove`, and panics if
+    /// the parent isn't a `Container` or the child isn't a `Widget`. If
+    /// [`add()`][add] puts some children anywhere else, like a title bar or a
+    /// popup, you'll need to take them out of there again here.
+    ///
+    /// [add]: #tymethod.add
+    fn remove(&self, parent: &Self::Parent, child: &Object) {
+        remove_from_container(parent.upcast_ref(), child)
+    }
+
+    /// Move a child to a new position among its siblings.
+    ///
+    /// This is only called when the order of children has changed, for the
+    /// children which need to move. The default implementation does nothing,
+    /// which is right for parents where the order of children is meaningless
+    /// or can't be changed after the fact.
+    fn reorder(
+        &self,
+        _parent: &Self::Parent,
+        _child: &Object,
+        _position: usize,
+        _child_props: ChildProps<'_>,
+    ) {
+    }
+}
+
+/// A `ChildAttach` with its parent type erased, so they can be stored together.
+trait AnyChildAttach {
+    fn add(
+        &self,
+        parent: &Object,
+        child: &Object,
+        index: usize,
+        total: usize,
+        props: ChildProps<'_>,
+    );
+    fn remove(&self, parent: &Object, child: &Object);
+    fn reorder(&self, parent: &Object, child: &Object, position: usize, props: ChildProps<'_>);
+}
+
+struct Erased<A>(A);
+
+impl<A: ChildAttach> Erased<A> {
+    fn parent<'a>(&self, parent: &'a Object) -> &'a A::Parent {
+        parent.downcast_ref().unwrap_or_else(|| {
+            panic!(
+                "ChildAttach for {} was given a {}",
+                A::Parent::static_type(),
+                parent.get_type()
+            )
+        })
+    }
+}
+
+impl<A: ChildAttach> AnyChildAttach for Erased<A> {
+    fn add(
+        &self,
+        parent: &Object,
+        child: &Object,
+        index: usize,
+        total: usize,
+        props: ChildProps<'_>,
+    ) {
+        self.0.add(self.parent(parent), child, index, total, props)
+    }
+
+    fn remove(&self, parent: &Object, child: &Object) {
+        self.0.remove(self.parent(parent), child)
+    }
+
+    fn reorder(&self, parent: &Object, child: &Object, position: usize, props: ChildProps<'_>) {
+        self.0.reorder(self.parent(parent), child, position, props)
+    }
+}
+
+type Registry = Vec<(Type, Rc<dyn AnyChildAttach>)>;
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(builtins());
+}
+
+fn insert<A: ChildAttach + 'static>(registry: &mut Registry, attach: A) {
+    let parent_type = A::Parent::static_type();
+    let attach = Rc::new(Erased(attach));
+    match registry.iter_mut().find(|(ty, _)| *ty == parent_type) {
+        Some(entry) => entry.1 = attach,
+        None => registry.push((parent_type, attach)),
+    }
+}
+
+/// Register a [`ChildAttach`][ChildAttach] for its parent type, replacing any
+/// previously registered for the same type.
+///
+/// The registry is per thread, so this needs to happen on the thread which
+/// runs your UI, before any parents of this type are built.
+///
+/// [ChildAttach]: trait.ChildAttach.html
+pub fn register<A: ChildAttach + 'static>(attach: A) {
+    REGISTRY.with(|registry| insert(&mut registry.borrow_mut(), attach))
+}
+
+/// Find the `ChildAttach` for the parent's type or its closest ancestor.
+fn lookup(parent: &Object) -> Option<Rc<dyn AnyChildAttach>> {
+    REGISTRY.with(|registry| {
+        let registry = registry.borrow();
+        let mut parent_type = Some(parent.get_type());
+        while let Some(ty) = parent_type {
+            if let Some((_, attach)) = registry.iter().find(|(entry, _)| *entry == ty) {
+                return Some(attach.clone());
+            }
+            parent_type = ty.parent();
+        }
+        None
+    })
+}
+
+pub(crate) fn add_child(
+    parent: &Object,
+    index: usize,
+    total: usize,
+    child_props: &[VProperty],
+    child: &Object,
+) {
+    match lookup(parent) {
+        Some(attach) => attach.add(parent, child, index, total, ChildProps::new(child_props)),
+        None => panic!("Don't know how to add children to a {}", parent.get_type()),
+    }
+}
+
+pub(crate) fn remove_child(parent: &Object, child: &Object) {
+    match lookup(parent) {
+        Some(attach) => attach.remove(parent, child),
+        None => panic!(
+            "Don't know how to remove a child from a {}",
+            parent.get_type()
+        ),
+    }
+}
+
+pub(crate) fn reorder_child(
+    parent: &Object,
+    position: usize,
+    child_props: &[VProperty],
+    child: &Object,
+) {
+    if let Some(attach) = lookup(parent) {
+        attach.reorder(parent, child, position, ChildProps::new(child_props));
+    }
+}
+
+fn remove_from_container(parent: &Object, child: &Object) {
+    // For a Container and a Widget child, we should always be able to call
+    // `Container::remove`.
+    match (
+        parent.downcast_ref::<Container>(),
+        child.downcast_ref::<Widget>(),
+    ) {
+        (Some(container), Some(widget)) => container.remove(widget),
+        (Some(_), None) => panic!(
+            "Containers can only contain Widgets but was asked to remove a {}.",
+            child.get_type()
+        ),
+        (None, _) => panic!(
+            "Don't know how to remove a child from a {}",
+            parent.get_type()
+        ),
+    }
+}
+
+/// Test whether the object in one of a parent's special slots is the given child.
+fn is_child<A: IsA<Object>>(slot: Option<A>, child: &Object) -> bool {
+    match slot {
+        Some(slot) => slot.upcast_ref::<Object>() == child,
+        None => false,
+    }
+}
+
+/// Set or unset a `Window`'s title bar.
+///
+/// GTK only wants this done before the window is realized, so if it already
+/// has been, we unrealize it first and map it again afterwards, which is what
+/// `set_titlebar()` would do anyway after complaining about it.
+fn set_titlebar(window: &Window, titlebar: Option<&Widget>) {
+    let was_mapped = window.get_mapped();
+    if window.get_realized() {
+        window.unrealize();
+    }
+    window.set_titlebar(titlebar);
+    if was_mapped {
+        window.map();
+    }
+}
+
+/// Add a widget to a `Window`, either as its title bar if it has the
+/// `Window::titlebar` child property, or as its main widget.
+fn add_to_window(window: &Window, name: &str, widget: &Widget, props: ChildProps<'_>) {
+    if props.contains("titlebar") {
+        set_titlebar(window, Some(widget));
+    } else if window.get_child().is_some() {
+        panic!(
+            "{} can only have 1 child besides its title bar, which needs the Window::titlebar \
+             child property.",
+            name
+        );
+    } else {
+        window.add(widget);
+    }
+}
+
+/// Remove a child from a `Window`, whether it's the title bar or the main widget.
+fn remove_from_window(window: &Window, child: &Object) {
+    if is_child(window.get_titlebar(), child) {
+        set_titlebar(window, None);
+    } else {
+        remove_from_container(window.upcast_ref(), child);
+    }
+}
+
+fn expect_widget<'a>(parent: &str, child: &'a Object) -> &'a Widget {
+    child.downcast_ref().unwrap_or_else(|| {
+        panic!(
+            "{}'s children must be Widgets, but {} was found.",
+            parent,
+            child.get_type()
+        )
+    })
+}
+
+/// Find the widget through which `child` is a child of `parent`.
+///
+/// This is the child itself for most containers, but a `ListBox` wraps its
+/// children in `ListBoxRow`s and a `FlowBox` wraps them in `FlowBoxChild`ren,
+/// unless they're already of that type, and it's the wrapper which has to be
+/// moved or removed.
+pub(crate) fn direct_child<P: IsA<Container>>(parent: &P, child: &Widget) -> Widget {
+    let parent = parent.upcast_ref::<Container>();
+    let mut widget = child.clone();
+    loop {
+        match widget.get_parent() {
+            Some(ref next) if next == parent => return widget,
+            Some(next) => widget = next,
+            None => return child.clone(),
+        }
+    }
+}
+
+// Gtk has many strange ways of adding children to a parent.
+fn builtins() -> Registry {
+    let mut registry = Vec::new();
+    insert(&mut registry, ContainerAttach);
+    insert(&mut registry, ApplicationAttach);
+    insert(&mut registry, MenuButtonAttach);
+    insert(&mut registry, MenuItemAttach);
+    insert(&mut registry, DialogAttach);
+    insert(&mut registry, ApplicationWindowAttach);
+    insert(&mut registry, WindowAttach);
+    insert(&mut registry, BinAttach);
+    insert(&mut registry, BoxAttach);
+    insert(&mut registry, HeaderBarAttach);
+    insert(&mut registry, GridAttach);
+    insert(&mut registry, NotebookAttach);
+    insert(&mut registry, ListBoxAttach);
+    insert(&mut registry, FlowBoxAttach);
+    insert(&mut registry, MenuAttach);
+    registry
+}
+
+struct ContainerAttach;
+
+impl ChildAttach for ContainerAttach {
+    type Parent = Container;
+
+    fn add(&self, parent: &Container, child: &Object, _: usize, _: usize, _: ChildProps<'_>) {
+        parent.add(expect_widget("Container", child));
+    }
+}
+
+struct ApplicationAttach;
+
+impl ChildAttach for ApplicationAttach {
+    type Parent = Application;
+
+    fn add(&self, parent: &Application, child: &Object, _: usize, _: usize, _: ChildProps<'_>) {
+        if let Some(window) = child.downcast_ref::<Window>() {
+            parent.add_window(window);
+        } else if let Some(action) = child.downcast_ref::<Action>() {
+            parent.add_action(action);
+        } else {
+            panic!(
+                "Application's children must be Windows or Actions, but {} was found.",
+                child.get_type()
+            );
+        }
+    }
+
+    fn remove(&self, parent: &Application, child: &Object) {
+        if let Some(window) = child.downcast_ref::<Window>() {
+            parent.remove_window(window);
+        } else if let Some(action) = child.downcast_ref::<Action>() {
+            parent.remove_action(&action.get_name().expect("Action unexpectedly has no name"));
+        } else {
+            panic!(
+                "Applications can only contain Windows, but was asked to remove a {}.",
+                child.get_type()
+            );
+        }
+    }
+}
+
+// MenuButton: can only have a single child, either a `Menu` set with
+// `set_popup` or any other `Widget` set with `set_popover`.
+struct MenuButtonAttach;
+
+impl ChildAttach for MenuButtonAttach {
+    type Parent = MenuButton;
+
+    fn add(&self, parent: &MenuButton, child: &Object, _: usize, total: usize, _: ChildProps<'_>) {
+        if total > 1 {
+            panic!(
+                "MenuButton can only have 1 child, but {} were found.",
+                total,
+            );
+        }
+        if let Some(menu) = child.downcast_ref::<Menu>() {
+            parent.set_popup(Some(menu));
+        } else {
+            parent.set_popover(Some(expect_widget("MenuButton", child)));
+        }
+    }
+
+    fn remove(&self, parent: &MenuButton, child: &Object) {
+        if is_child(parent.get_popup(), child) {
+            parent.set_popup(None::<&Widget>);
+        } else if is_child(parent.get_popover(), child) {
+            parent.set_popover(None::<&Widget>);
+        } else {
+            remove_from_container(parent.upcast_ref(), child);
+        }
+    }
+}
+
+// MenuItem: single child, must be a `Menu`, set with `set_submenu`.
+struct MenuItemAttach;
+
+impl ChildAttach for MenuItemAttach {
+    type Parent = MenuItem;
+
+    fn add(&self, parent: &MenuItem, child: &Object, _: usize, total: usize, _: ChildProps<'_>) {
+        if total > 1 {
+            panic!("MenuItem can only have 1 child, but {} were found.", total);
+        }
+        if let Some(menu) = child.downcast_ref::<Menu>() {
+            parent.set_submenu(Some(menu));
+        } else {
+            panic!(
+                "MenuItem can only take children of type Menu, but {} was found.",
+                child.get_type()
+            );
+        }
+    }
+
+    fn remove(&self, parent: &MenuItem, child: &Object) {
+        if is_child(parent.get_submenu(), child) {
+            parent.set_submenu(None::<&Menu>);
+        } else {
+            remove_from_container(parent.upcast_ref(), child);
+        }
+    }
+}
+
+// Dialog: children must be added to the Dialog's content area through
+// get_content_area().
+struct DialogAttach;
+
+impl ChildAttach for DialogAttach {
+    type Parent = Dialog;
+
+    fn add(&self, parent: &Dialog, child: &Object, _: usize, _: usize, _: ChildProps<'_>) {
+        parent
+            .get_content_area()
+            .add(expect_widget("Dialog", child));
+    }
+
+    fn remove(&self, parent: &Dialog, child: &Object) {
+        remove_from_container(parent.get_content_area().upcast_ref(), child);
+    }
+}
+
+// ApplicationWindow: takes any number of Actions, optionally one
+// ShortcutsWindow added with `set_help_overlay()`, and Widgets like a Window.
+struct ApplicationWindowAttach;
+
+impl ChildAttach for ApplicationWindowAttach {
+    type Parent = ApplicationWindow;
+
+    fn add(
+        &self,
+        parent: &ApplicationWindow,
+        child: &Object,
+        _: usize,
+        _: usize,
+        props: ChildProps<'_>,
+    ) {
+        if let Some(action) = child.downcast_ref::<Action>() {
+            parent.add_action(action);
+        } else if let Some(help_overlay) = child.downcast_ref::<ShortcutsWindow>() {
+            parent.set_help_overlay(Some(help_overlay));
+        } else if let Some(widget) = child.downcast_ref::<Widget>() {
+            add_to_window(parent.upcast_ref(), "ApplicationWindow", widget, props);
+        } else {
+            panic!(
+                "ApplicationWindow's children must be Actions or Widgets, but {} was found.",
+                child.get_type()
+            );
+        }
+    }
+
+    fn remove(&self, parent: &ApplicationWindow, child: &Object) {
+        if let Some(action) = child.downcast_ref::<Action>() {
+            parent.remove_action(&action.get_name().expect("Action unexpectedly has no name"));
+        } else if is_child(parent.get_help_overlay(), child) {
+            parent.set_help_overlay(None::<&ShortcutsWindow>);
+        } else {
+            remove_from_window(parent.upcast_ref(), child);
+        }
+    }
+}
+
+// Window: takes a main widget, and a title bar widget if it has the
+// Window::titlebar=true child property (which is faked in ext.rs). Either can
+// come and go as the window is re-rendered.
+struct WindowAttach;
+
+impl ChildAttach for WindowAttach {
+    type Parent = Window;
+
+    fn add(&self, parent: &Window, child: &Object, _: usize, _: usize, props: ChildProps<'_>) {
+        add_to_window(parent, "Window", expect_widget("Window", child), props);
+    }
+
+    fn remove(&self, parent: &Window, child: &Object) {
+        remove_from_window(parent, child);
+    }
+}
+
+// Bin: can only have a single child.
+struct BinAttach;
+
+impl ChildAttach for BinAttach {
+    type Parent = Bin;
+
+    fn add(&self, parent: &Bin, child: &Object, _: usize, total: usize, _: ChildProps<'_>) {
+        if total > 1 {
+            panic!("Bins can only have 1 child, but {} were found.", total);
+        }
+        parent.add(expect_widget("Bin", child));
+    }
+}
+
+// Box: added normally, except one widget can be added using
+// set_center_widget() if it has the center_widget=true child property (which
+// is faked in ext.rs). More than one child with this property is undefined
+// behaviour.
+struct BoxAttach;
+
+impl ChildAttach for BoxAttach {
+    type Parent = GtkBox;
+
+    fn add(&self, parent: &GtkBox, child: &Object, _: usize, _: usize, props: ChildProps<'_>) {
+        let widget = expect_widget("Box", child);
+        if props.contains("center_widget") {
+            parent.set_center_widget(Some(widget));
+        } else {
+            parent.add(widget);
+        }
+    }
+
+    fn remove(&self, parent: &GtkBox, child: &Object) {
+        if is_child(parent.get_center_widget(), child) {
+            parent.set_center_widget(None::<&Widget>);
+        } else {
+            remove_from_container(parent.upcast_ref(), child);
+        }
+    }
+
+    fn reorder(&self, parent: &GtkBox, child: &Object, position: usize, props: ChildProps<'_>) {
+        if let Some(widget) = child.downcast_ref::<Widget>() {
+            if !props.contains("center_widget") {
+                parent.reorder_child(widget, position as i32);
+            }
+        }
+    }
+}
+
+// HeaderBar: added normally, except one widget can be added using
+// set_custom_title if it has the custom_title=true child property (which is
+// faked in ext.rs). More than one child with this property is undefined
+// behaviour.
+struct HeaderBarAttach;
+
+impl ChildAttach for HeaderBarAttach {
+    type Parent = HeaderBar;
+
+    fn add(&self, parent: &HeaderBar, child: &Object, _: usize, _: usize, props: ChildProps<'_>) {
+        let widget = expect_widget("HeaderBar", child);
+        if props.contains("custom_title") {
+            parent.set_custom_title(Some(widget));
+        } else {
+            parent.add(widget);
+        }
+    }
+
+    fn remove(&self, parent: &HeaderBar, child: &Object) {
+        if is_child(parent.get_custom_title(), child) {
+            parent.set_custom_title(None::<&Widget>);
+        } else {
+            remove_from_container(parent.upcast_ref(), child);
+        }
+    }
+
+    fn reorder(&self, parent: &HeaderBar, child: &Object, position: usize, props: ChildProps<'_>) {
+        if let Some(widget) = child.downcast_ref::<Widget>() {
+            if !props.contains("custom_title") {
+                parent.set_child_position(widget, position as i32);
+            }
+        }
+    }
+}
+
+struct GridAttach;
+
+impl ChildAttach for GridAttach {
+    type Parent = Grid;
+
+    fn add(&self, parent: &Grid, child: &Object, _: usize, _: usize, _: ChildProps<'_>) {
+        // by default we put widgets in the top left corner of the grid with
+        // row and col span of 1; this would typically get overridden via
+        // props but setting the default is important in order to avoid making
+        // the user specify these for every single child widget
+        parent.attach(expect_widget("Grid", child), 0, 0, 1, 1);
+    }
+}
+
+// Notebook: added normally, except one widget can be added using
+// set_action_widget if it has the action_widget_start or action_widget_end
+// child property (which are faked in ext.rs). More than one child with each
+// of these properties is undefined behaviour.
+struct NotebookAttach;
+
+impl NotebookAttach {
+    fn is_action_widget(props: ChildProps<'_>) -> bool {
+        props.contains("action_widget_start") || props.contains("action_widget_end")
+    }
+}
+
+impl ChildAttach for NotebookAttach {
+    type Parent = Notebook;
+
+    fn add(&self, parent: &Notebook, child: &Object, _: usize, _: usize, props: ChildProps<'_>) {
+        let widget = expect_widget("Notebook", child);
+        if props.contains("action_widget_start") {
+            parent.set_action_widget(widget, gtk::PackType::Start);
+        } else if props.contains("action_widget_end") {
+            parent.set_action_widget(widget, gtk::PackType::End);
+        } else {
+            parent.add(widget);
+        }
+    }
+
+    fn remove(&self, parent: &Notebook, child: &Object) {
+        for pack_type in &[gtk::PackType::Start, gtk::PackType::End] {
+            if is_child(parent.get_action_widget(*pack_type), child) {
+                ffi::unset_action_widget(parent, *pack_type);
+                return;
+            }
+        }
+        remove_from_container(parent.upcast_ref(), child);
+    }
+
+    fn reorder(&self, parent: &Notebook, child: &Object, position: usize, props: ChildProps<'_>) {
+        if let Some(widget) = child.downcast_ref::<Widget>() {
+            if !Self::is_action_widget(props) {
+                parent.reorder_child(widget, Some(position as u32));
+            }
+        }
+    }
+}
+
+// ListBox and FlowBox can't move a row in place, so we take it out and insert
+// it again at the new position.
+struct ListBoxAttach;
+
+impl ChildAttach for ListBoxAttach {
+    type Parent = ListBox;
+
+    fn add(&self, parent: &ListBox, child: &Object, _: usize, _: usize, _: ChildProps<'_>) {
+        parent.add(expect_widget("ListBox", child));
+    }
+
+    fn remove(&self, parent: &ListBox, child: &Object) {
+        parent.remove(&direct_child(parent, expect_widget("ListBox", child)));
+    }
+
+    fn reorder(&self, parent: &ListBox, child: &Object, position: usize, _: ChildProps<'_>) {
+        if let Some(widget) = child.downcast_ref::<Widget>() {
+            // Move the row the widget was wrapped in, if any, rather than
+            // have the widget wrapped in a new one.
+            let row = direct_child(parent, widget);
+            parent.remove(&row);
+            parent.insert(&row, position as i32);
+        }
+    }
+}
+
+struct FlowBoxAttach;
+
+impl ChildAttach for FlowBoxAttach {
+    type Parent = FlowBox;
+
+    fn add(&self, parent: &FlowBox, child: &Object, _: usize, _: usize, _: ChildProps<'_>) {
+        parent.add(expect_widget("FlowBox", child));
+    }
+
+    fn remove(&self, parent: &FlowBox, child: &Object) {
+        parent.remove(&direct_child(parent, expect_widget("FlowBox", child)));
+    }
+
+    fn reorder(&self, parent: &FlowBox, child: &Object, position: usize, _: ChildProps<'_>) {
+        if let Some(widget) = child.downcast_ref::<Widget>() {
+            let wrapper = direct_child(parent, widget);
+            parent.remove(&wrapper);
+            parent.insert(&wrapper, position as i32);
+        }
+    }
+}
+
+struct MenuAttach;
+
+impl ChildAttach for MenuAttach {
+    type Parent = Menu;
+
+    fn add(&self, parent: &Menu, child: &Object, _: usize, _: usize, _: ChildProps<'_>) {
+        parent.add(expect_widget("Menu", child));
+    }
+
+    fn reorder(&self, parent: &Menu, child: &Object, position: usize, _: ChildProps<'_>) {
+        if let Some(widget) = child.downcast_ref::<Widget>() {
+            parent.reorder_child(widget, position as i32);
+        }
+    }
+}
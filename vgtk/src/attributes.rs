@@ -0,0 +1,161 @@
+use glib::ToValue;
+
+use std::fmt::{Debug, Error, Formatter};
+use std::rc::Rc;
+
+use crate::properties::{construct_by_name, reset_property_by_name, set_property_by_name};
+use crate::vnode::VProperty;
+
+/// A set of widget properties which can be passed around and given to a widget
+/// all at once with a `..` attribute in [`gtk!`][gtk!].
+///
+/// This lets a component which wraps a widget take any of the widget's
+/// properties from its own user without listing each of them as a field of its
+/// `Properties`. Properties are named the way you'd write them as attributes,
+/// as in `tooltip_text`, and are set through their GObject names like `prop:`
+/// attributes are, so their types are only checked at runtime.
+///
+/// Attributes written out on the widget take precedence over the ones in the
+/// spread. A property which was in the spread on the previous render but isn't
+/// any more is reset to its default value.
+///
+/// ```rust,no_run
+/// # use vgtk::{gtk, Attributes, Component, VNode};
+/// # use vgtk::lib::gtk::*;
+/// #[derive(Clone, Debug, Default)]
+/// pub struct SaveButton {
+///     pub common: Attributes,
+/// }
+///
+/// impl Component for SaveButton {
+///     type Message = ();
+///     type Properties = Self;
+///
+///     fn create(props: Self) -> Self {
+///         props
+///     }
+///
+///     fn change(&mut self, props: Self) -> vgtk::UpdateAction<Self> {
+///         *self = props;
+///         vgtk::UpdateAction::Render
+///     }
+///
+///     fn view(&self) -> VNode<Self> {
+///         gtk! {
+///             <Button label="Save" ..self.common />
+///         }
+///     }
+/// }
+///
+/// # fn view() -> VNode<SaveButton> {
+/// let common = Attributes::new()
+///     .set("sensitive", false)
+///     .set("tooltip_text", "Nothing to save".to_string());
+/// gtk! {
+///     <@SaveButton common=common />
+/// }
+/// # }
+/// ```
+///
+/// [gtk!]: macro.gtk.html
+#[derive(Clone, Default)]
+pub struct Attributes {
+    properties: Vec<VProperty>,
+}
+
+impl Attributes {
+    /// Make an empty set of attributes.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a property to the set, replacing any property of the same name
+    /// which is already in it.
+    pub fn set<A>(mut self, name: &'static str, value: A) -> Self
+    where
+        A: ToValue + PartialEq + 'static,
+    {
+        let value = Rc::new(value);
+        let construct = construct_by_name(name, value.clone());
+        let property = VProperty {
+            name,
+            value: value.clone(),
+            set: Rc::new(move |object, _parent, previous| {
+                set_property_by_name(object, name, &*value, previous)
+            }),
+            construct: Some(construct),
+            unset: Some(Rc::new(move |object, _parent, _previous| {
+                reset_property_by_name(object, name)
+            })),
+        };
+        match self.properties.iter_mut().find(|prop| prop.name == name) {
+            Some(prop) => *prop = property,
+            None => self.properties.push(property),
+        }
+        self
+    }
+
+    /// Test whether the set has no properties in it.
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    /// Add the properties to a widget's list of properties, leaving out the
+    /// ones it already has.
+    #[doc(hidden)]
+    pub fn spread(&self, properties: &mut Vec<VProperty>) {
+        let given = properties.len();
+        for prop in &self.properties {
+            if !properties[..given]
+                .iter()
+                .any(|existing| existing.name == prop.name)
+            {
+                properties.push(prop.clone());
+            }
+        }
+    }
+}
+
+impl Debug for Attributes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.debug_set()
+            .entries(self.properties.iter().map(|prop| prop.name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Attributes;
+    use crate::vdom::PropertyValues;
+    use gio::{ActionExt, SimpleAction};
+    use glib::Cast;
+
+    #[test]
+    fn properties_given_explicitly_take_precedence() {
+        let common = Attributes::new()
+            .set("enabled", false)
+            .set("name", "common".to_string());
+        let mut properties = Attributes::new()
+            .set("name", "explicit".to_string())
+            .properties;
+        common.spread(&mut properties);
+        let names: Vec<_> = properties.iter().map(|prop| prop.name).collect();
+        assert_eq!(names, vec!["name", "enabled"]);
+    }
+
+    #[test]
+    fn properties_leaving_the_spread_are_reset() {
+        let action = SimpleAction::new("save", None);
+        let objects = [action.clone().upcast()];
+        let mut applied = PropertyValues::default();
+        let mut properties = Vec::new();
+        Attributes::new()
+            .set("enabled", false)
+            .spread(&mut properties);
+        applied.apply(&properties, &objects, None);
+        assert!(!action.get_enabled());
+        applied.apply(&[], &objects, None);
+        assert!(action.get_enabled());
+    }
+}
@@ -0,0 +1,170 @@
+use glib::Object;
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::{Debug, Error, Formatter};
+use std::rc::Rc;
+
+use crate::component::Component;
+use crate::scope::Scope;
+use crate::vdom::{AnyChildrenState, ChildrenState};
+use crate::vnode::VNode;
+
+/// A property for passing child nodes to a sub-[`Component`][Component].
+///
+/// When you give a subcomponent children, like this:
+///
+/// ```rust,ignore
+/// <@Card title="Hello">
+///     <Label label="Hello Joe!" />
+/// </@Card>
+/// ```
+///
+/// the [`gtk!`][gtk!] macro collects them into a `Children` value and assigns it to the
+/// `children` property on the subcomponent's [`Component::Properties`][Properties]. You can also
+/// pass children in named slots, which are assigned to the property of the same name instead:
+///
+/// ```rust,ignore
+/// <@Card title="Hello">
+///     <@Card::buttons>
+///         <Button label="OK" />
+///     </@Card::buttons>
+///     <Label label="Hello Joe!" />
+/// </@Card>
+/// ```
+///
+/// The subcomponent puts its children in its own view with [`render()`][render]. They still
+/// belong to the component which created them, so their signal handlers send messages to that
+/// component rather than the subcomponent.
+///
+/// ```rust,no_run
+/// # use vgtk::{gtk, VNode, Component, Children};
+/// # use vgtk::lib::gtk::{Frame, FrameExt, Box, BoxExt, Orientation, OrientableExt};
+/// #[derive(Clone, Debug, Default)]
+/// pub struct Card {
+///     pub title: String,
+///     pub buttons: Children,
+///     pub children: Children,
+/// }
+///
+/// impl Component for Card {
+///     type Message = ();
+///     type Properties = Self;
+/// #   fn create(props: Self) -> Self { props }
+///
+///     fn view(&self) -> VNode<Self> {
+///         gtk! {
+///             <Frame label=self.title.clone()>
+///                 <Box orientation=Orientation::Vertical>
+///                     { self.children.render() }
+///                     <Box>
+///                         { self.buttons.render() }
+///                     </Box>
+///                 </Box>
+///             </Frame>
+///         }
+///     }
+/// }
+/// ```
+///
+/// The [`Default`][Default] implementation for `Children` constructs an empty list of children,
+/// which renders nothing.
+///
+/// [Component]: trait.Component.html
+/// [Properties]: trait.Component.html#associatedtype.Properties
+/// [gtk!]: macro.gtk.html
+/// [render]: #method.render
+/// [Default]: https://doc.rust-lang.org/std/default/trait.Default.html
+#[derive(Clone, Default)]
+pub struct Children(pub(crate) Option<Rc<dyn AnyChildNodes>>);
+
+impl Children {
+    /// Render the children in place of a node in a view.
+    ///
+    /// Children can only be rendered in one place at a time.
+    pub fn render<Model: Component>(&self) -> VNode<Model> {
+        match self.0 {
+            Some(_) => VNode::Children(self.clone()),
+            None => VNode::Fragment(Vec::new()),
+        }
+    }
+
+    /// Test whether the list of children is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub(crate) fn build(&self, parent: Option<&Object>) -> Box<dyn AnyChildrenState> {
+        self.nodes().build(parent)
+    }
+
+    pub(crate) fn nodes(&self) -> Rc<dyn AnyChildNodes> {
+        self.0
+            .clone()
+            .expect("empty children should have been rendered as an empty fragment")
+    }
+}
+
+impl PartialEq for Children {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(left), Some(right)) => std::ptr::eq(
+                Rc::as_ptr(left) as *const (),
+                Rc::as_ptr(right) as *const (),
+            ),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Debug for Children {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "Children()")
+    }
+}
+
+/// The nodes in a list of `Children`, along with the scope of the component
+/// which created them.
+///
+/// The scope isn't known when the nodes are created, so it's filled in when the
+/// component they're passed to is built or patched.
+pub(crate) struct ChildNodes<Model: Component> {
+    pub(crate) nodes: Vec<VNode<Model>>,
+    scope: RefCell<Option<Scope<Model>>>,
+}
+
+impl<Model: 'static + Component> ChildNodes<Model> {
+    pub(crate) fn new(nodes: Vec<VNode<Model>>) -> Self {
+        ChildNodes {
+            nodes,
+            scope: RefCell::new(None),
+        }
+    }
+
+    pub(crate) fn bind(&self, scope: &Scope<Model>) {
+        *self.scope.borrow_mut() = Some(scope.clone());
+    }
+
+    pub(crate) fn scope(&self) -> Scope<Model> {
+        self.scope
+            .borrow()
+            .clone()
+            .expect("children were rendered before they were given a scope")
+    }
+}
+
+pub(crate) trait AnyChildNodes {
+    fn into_any(self: Rc<Self>) -> Rc<dyn Any>;
+    fn build(self: Rc<Self>, parent: Option<&Object>) -> Box<dyn AnyChildrenState>;
+}
+
+impl<Model: 'static + Component> AnyChildNodes for ChildNodes<Model> {
+    fn into_any(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+
+    fn build(self: Rc<Self>, parent: Option<&Object>) -> Box<dyn AnyChildrenState> {
+        Box::new(ChildrenState::build(self, parent))
+    }
+}
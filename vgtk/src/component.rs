@@ -1,6 +1,6 @@
 use futures::{
     channel::mpsc::{unbounded, UnboundedSender},
-    future::FutureExt,
+    future::{AbortHandle, AbortRegistration, Abortable, FutureExt},
     stream::{select, Stream},
     task::{Context, Poll},
     StreamExt,
@@ -9,16 +9,21 @@ use glib::{Cast, MainContext, Object, ObjectExt, WeakRef};
 use gtk::{Application, GtkApplicationExt, Widget, WidgetExt, Window};
 
 use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::{Debug, Error, Formatter};
 use std::future::Future;
 use std::pin::Pin;
+use std::rc::Rc;
 use std::sync::RwLock;
 
 use colored::Colorize;
 use log::{debug, trace};
 
 use crate::scope::{AnyScope, Scope};
-use crate::vdom::State;
+use crate::stylesheet::Stylesheet;
+use crate::subscription::{RunningSubscriptions, Subscriptions};
+use crate::vdom::{Placement, State};
 use crate::vnode::VNode;
 
 /// An action resulting from a [`Component::update()`](trait.Component.html#method.update).
@@ -71,6 +76,32 @@ pub enum UpdateAction<C: Component> {
     /// [defer]: #method.defer
     /// [Future]: https://doc.rust-lang.org/std/future/trait.Future.html
     Defer(Pin<Box<dyn Future<Output = C::Message> + 'static>>),
+    /// Run an async task like [`Defer`][Defer], but allow it to be cancelled
+    /// through a [`JobHandle`][JobHandle].
+    ///
+    /// You should call [`UpdateAction::defer_cancellable()`][defer_cancellable]
+    /// to construct this.
+    ///
+    /// [Defer]: #variant.Defer
+    /// [JobHandle]: struct.JobHandle.html
+    /// [defer_cancellable]: #method.defer_cancellable
+    DeferCancellable(
+        Pin<Box<dyn Future<Output = C::Message> + 'static>>,
+        AbortRegistration,
+    ),
+    /// Call [`Component::update()`][update] again straight away with the given
+    /// message, before any other messages waiting for the component.
+    ///
+    /// [update]: trait.Component.html#method.update
+    Message(C::Message),
+    /// Perform several actions, in order.
+    ///
+    /// The component is rendered only once, however many of them ask for it.
+    Batch(Vec<UpdateAction<C>>),
+    /// Quit the application, as if you'd called [`vgtk::quit()`][quit].
+    ///
+    /// [quit]: fn.quit.html
+    Quit,
 }
 
 impl<C: Component> UpdateAction<C> {
@@ -80,6 +111,147 @@ impl<C: Component> UpdateAction<C> {
     pub fn defer(job: impl Future<Output = C::Message> + 'static) -> Self {
         UpdateAction::Defer(job.boxed_local())
     }
+
+    /// Construct an action which sends a message back to the component.
+    pub fn message(message: C::Message) -> Self {
+        UpdateAction::Message(message)
+    }
+
+    /// Construct an action which performs several actions.
+    ///
+    /// ```rust,no_run
+    /// # use vgtk::{gtk, Component, UpdateAction, VNode};
+    /// # use vgtk::lib::gtk::Box;
+    /// # async fn save() {}
+    /// # #[derive(Clone, Debug)] enum Message { Save, Saved, Tick }
+    /// # #[derive(Default)] struct Editor { saving: bool }
+    /// # impl Component for Editor {
+    /// #     type Message = Message; type Properties = ();
+    /// #     fn view(&self) -> VNode<Self> { gtk!{ <Box/> } }
+    /// fn update(&mut self, message: Message) -> UpdateAction<Self> {
+    ///     match message {
+    ///         Message::Save => {
+    ///             self.saving = true;
+    ///             UpdateAction::batch(vec![
+    ///                 UpdateAction::Render,
+    ///                 UpdateAction::defer(async {
+    ///                     save().await;
+    ///                     Message::Saved
+    ///                 }),
+    ///             ])
+    ///         }
+    ///         // ...
+    /// #       _ => UpdateAction::None,
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn batch(actions: impl IntoIterator<Item = UpdateAction<C>>) -> Self {
+        UpdateAction::Batch(actions.into_iter().collect())
+    }
+
+    /// Construct an action which quits the application.
+    pub fn quit() -> Self {
+        UpdateAction::Quit
+    }
+
+    /// Perform this action followed by another one.
+    ///
+    /// This is a shorthand for [`UpdateAction::batch()`][batch], for instance
+    /// `UpdateAction::Render.and_then(UpdateAction::message(Message::Scroll))`.
+    ///
+    /// [batch]: #method.batch
+    pub fn and_then(self, next: impl Into<UpdateAction<C>>) -> Self {
+        let next = next.into();
+        match self {
+            UpdateAction::None => next,
+            UpdateAction::Batch(mut actions) => {
+                actions.push(next);
+                UpdateAction::Batch(actions)
+            }
+            action => UpdateAction::Batch(vec![action, next]),
+        }
+    }
+
+    /// Construct a deferred action given a [`Future`][Future], along with a
+    /// [`JobHandle`][JobHandle] you can use to cancel it.
+    ///
+    /// This is useful when a job can be overtaken by a newer one, like a search
+    /// which is restarted every time the user types into a search box:
+    ///
+    /// ```rust,no_run
+    /// # use vgtk::{gtk, Component, JobHandle, UpdateAction, VNode};
+    /// # use vgtk::lib::gtk::Box;
+    /// # async fn search(_: &str) -> Vec<String> { Vec::new() }
+    /// #[derive(Clone, Debug)]
+    /// enum Message {
+    ///     Search(String),
+    ///     Results(Vec<String>),
+    /// }
+    ///
+    /// #[derive(Default)]
+    /// struct Search {
+    ///     job: Option<JobHandle>,
+    ///     results: Vec<String>,
+    /// }
+    ///
+    /// impl Component for Search {
+    ///     type Message = Message;
+    ///     type Properties = ();
+    /// #   fn view(&self) -> VNode<Self> { gtk!{ <Box/> } }
+    ///
+    ///     fn update(&mut self, message: Message) -> UpdateAction<Self> {
+    ///         match message {
+    ///             Message::Search(query) => {
+    ///                 if let Some(job) = self.job.take() {
+    ///                     job.cancel();
+    ///                 }
+    ///                 let (action, job) = UpdateAction::defer_cancellable(async move {
+    ///                     Message::Results(search(&query).await)
+    ///                 });
+    ///                 self.job = Some(job);
+    ///                 action
+    ///             }
+    ///             Message::Results(results) => {
+    ///                 self.job = None;
+    ///                 self.results = results;
+    ///                 UpdateAction::Render
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [Future]: https://doc.rust-lang.org/std/future/trait.Future.html
+    /// [JobHandle]: struct.JobHandle.html
+    pub fn defer_cancellable(job: impl Future<Output = C::Message> + 'static) -> (Self, JobHandle) {
+        let (handle, registration) = AbortHandle::new_pair();
+        (
+            UpdateAction::DeferCancellable(job.boxed_local(), registration),
+            JobHandle(handle),
+        )
+    }
+}
+
+/// A handle for cancelling a job started with
+/// [`UpdateAction::defer_cancellable()`][defer_cancellable].
+///
+/// Dropping the handle doesn't cancel the job. Jobs are always cancelled when
+/// the component which started them is unmounted, whether they have a handle or
+/// not.
+///
+/// [defer_cancellable]: enum.UpdateAction.html#method.defer_cancellable
+#[derive(Clone, Debug)]
+pub struct JobHandle(AbortHandle);
+
+impl JobHandle {
+    /// Cancel the job.
+    ///
+    /// If the job hasn't completed yet, it's dropped without sending its message.
+    /// Otherwise, this has no effect.
+    pub fn cancel(&self) {
+        self.0.abort();
+    }
 }
 
 impl<C, F> From<F> for UpdateAction<C>
@@ -141,7 +313,8 @@ pub trait Component: Default + Unpin {
     ///
     /// If you need to perform I/O, you can return `UpdateAction::Defer`, which
     /// will run an async action and call `Component::update()` again with its
-    /// result.
+    /// result. To do several of these things at once, return an
+    /// `UpdateAction::batch()`.
     ///
     /// Otherwise, return `UpdateAction::None`.
     fn update(&mut self, _msg: Self::Message) -> UpdateAction<Self> {
@@ -182,6 +355,126 @@ pub trait Component: Default + Unpin {
     /// need to be aware of when this happens.
     fn unmounted(&mut self) {}
 
+    /// This method is called after the `Component`'s view has been rendered
+    /// into GTK widgets, with `first_render` set to `true` the first time.
+    /// A top level component is told about its first render after
+    /// [`mounted()`][mounted] has been called.
+    ///
+    /// Use it for imperative work which needs the widgets to be up to date,
+    /// like focusing a newly added row through a [`WidgetRef`][WidgetRef] or
+    /// scrolling a log view to the bottom. Unlike during a render, signal
+    /// handlers triggered from here will send their messages as usual.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// [WidgetRef]: struct.WidgetRef.html
+    /// [mounted]: #method.mounted
+    fn rendered(&mut self, _first_render: bool) {}
+
+    /// Declare the long lived sources of messages the `Component` should be
+    /// listening to, like timers or file monitors.
+    ///
+    /// This is called when the `Component` is created and again after every
+    /// update or change of properties. Any [`Subscription`][Subscription]s
+    /// which weren't there the last time are started, and any which have gone
+    /// away are stopped. They're all stopped when the `Component` is unmounted.
+    /// A subscription whose stream ends isn't started again until its ID has
+    /// gone away and come back.
+    ///
+    /// The default implementation returns no subscriptions.
+    ///
+    /// ```rust,no_run
+    /// # use std::time::Duration;
+    /// # use vgtk::{gtk, Component, Subscription, Subscriptions, UpdateAction, VNode};
+    /// # use vgtk::lib::gtk::{Label, LabelExt};
+    /// #[derive(Clone, Debug, Default)]
+    /// struct Clock {
+    ///     seconds: usize,
+    /// }
+    ///
+    /// #[derive(Clone, Debug)]
+    /// enum Message {
+    ///     Tick,
+    /// }
+    ///
+    /// impl Component for Clock {
+    ///     type Message = Message;
+    ///     type Properties = ();
+    ///
+    ///     fn update(&mut self, message: Message) -> UpdateAction<Self> {
+    ///         match message {
+    ///             Message::Tick => {
+    ///                 self.seconds += 1;
+    ///                 UpdateAction::Render
+    ///             }
+    ///         }
+    ///     }
+    ///
+    ///     fn subscriptions(&self) -> Subscriptions<Message> {
+    ///         Subscription::interval("tick", Duration::from_secs(1), || Message::Tick).into()
+    ///     }
+    ///
+    ///     fn view(&self) -> VNode<Self> {
+    ///         gtk! {
+    ///             <Label label=format!("{} seconds", self.seconds) />
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [Subscription]: struct.Subscription.html
+    fn subscriptions(&self) -> Subscriptions<Self::Message> {
+        Subscriptions::new()
+    }
+
+    /// Provide a CSS stylesheet for the `Component`, which only applies to the
+    /// widgets in its own view.
+    ///
+    /// The stylesheet is scoped by giving the root widgets of each instance's
+    /// view a style class unique to the component's type, and rewriting each
+    /// selector to match only that widget and its descendants, including
+    /// those in any subcomponents. A selector starting with a combinator, as
+    /// in `> label`, is relative to the root widget. The stylesheet is
+    /// installed on the default screen while any instance of the component is
+    /// mounted, and removed again when the last one is unmounted. If GTK can't
+    /// parse the stylesheet, a warning is logged and it isn't installed.
+    ///
+    /// Because a stylesheet is scoped to widgets, it has no effect on a
+    /// component whose view is an `Application`. Give it to its window's
+    /// component instead.
+    ///
+    /// The default implementation returns `None`.
+    ///
+    /// ```rust,no_run
+    /// # use vgtk::{gtk, Component, VNode};
+    /// # use vgtk::lib::gtk::{Box, Label, LabelExt};
+    /// # #[derive(Default)] struct Card;
+    /// impl Component for Card {
+    /// #   type Message = ();
+    /// #   type Properties = ();
+    ///     fn stylesheet() -> Option<&'static str> {
+    ///         Some("
+    ///             box { padding: 12px; }
+    ///             label.title { font-weight: bold; }
+    ///         ")
+    ///     }
+    ///
+    ///     fn view(&self) -> VNode<Self> {
+    ///         gtk! {
+    ///             <Box>
+    ///                 <Label label="Title" class="title" />
+    ///             </Box>
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn stylesheet() -> Option<&'static str>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
     /// Build a `VNode` tree to represent your UI.
     ///
     /// This is called whenever the `Component` needs to re-render, and its UI
@@ -263,6 +556,7 @@ where
     pub(crate) fn new(
         props: C::Properties,
         parent: Option<&Object>,
+        placement: Rc<Placement>,
         parent_scope: Option<&Scope<P>>,
     ) -> Self {
         let (sys_send, sys_recv) = unbounded();
@@ -283,15 +577,24 @@ where
         };
         let state = C::create(props);
         let initial_view = state.view();
-        let ui_state = State::build_root(&initial_view, parent, &scope);
+        let ui_state = State::build_root(&initial_view, parent, &placement, &scope);
+        let task = ComponentTask {
+            scope,
+            parent_scope: parent_scope.cloned(),
+            state,
+            ui_state: Some(ui_state),
+            parent: parent.cloned(),
+            placement,
+            channel,
+            first_render: true,
+            subscriptions: RunningSubscriptions::new(),
+            jobs: Default::default(),
+            stylesheet: Stylesheet::acquire::<C>(),
+        };
+        task.update_placement();
+        task.update_stylesheet();
         PartialComponentTask {
-            task: ComponentTask {
-                scope,
-                parent_scope: parent_scope.cloned(),
-                state,
-                ui_state: Some(ui_state),
-                channel,
-            },
+            task,
             view: initial_view,
             sender: sys_send,
         }
@@ -305,16 +608,25 @@ where
         if let Some(ref mut ui_state) = self.task.ui_state {
             ui_state.build_children(&self.view, &self.task.scope);
         }
+        self.task.update_subscriptions();
         (self.sender, self.task)
     }
 
     pub(crate) fn object(&self) -> Object {
-        self.task.ui_state.as_ref().unwrap().object().clone()
+        self.task
+            .ui_state
+            .as_ref()
+            .and_then(State::object)
+            .expect("component has no root object")
     }
 
     pub(crate) fn scope(&self) -> Scope<C> {
         self.task.scope.clone()
     }
+
+    pub(crate) fn channel(&self) -> UnboundedSender<ComponentMessage<C>> {
+        self.sender.clone()
+    }
 }
 
 pub(crate) struct ComponentTask<C, P>
@@ -326,7 +638,48 @@ where
     parent_scope: Option<Scope<P>>,
     state: C,
     ui_state: Option<State<C>>,
+    parent: Option<Object>,
+    placement: Rc<Placement>,
     channel: Pin<Box<dyn Stream<Item = ComponentMessage<C>>>>,
+    first_render: bool,
+    subscriptions: RunningSubscriptions,
+    jobs: Rc<RefCell<RunningJobs>>,
+    stylesheet: Option<Stylesheet>,
+}
+
+/// The deferred jobs a component has started which haven't completed yet.
+///
+/// Any jobs still running are aborted when this is dropped.
+#[derive(Default)]
+struct RunningJobs {
+    next_id: usize,
+    handles: HashMap<usize, AbortHandle>,
+}
+
+impl RunningJobs {
+    fn register(&mut self) -> (usize, AbortRegistration) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let (handle, registration) = AbortHandle::new_pair();
+        self.handles.insert(id, handle);
+        (id, registration)
+    }
+
+    fn remove(&mut self, id: usize) {
+        self.handles.remove(&id);
+    }
+
+    fn abort(&mut self) {
+        for (_, handle) in self.handles.drain() {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for RunningJobs {
+    fn drop(&mut self) {
+        self.abort();
+    }
 }
 
 impl<C, P> ComponentTask<C, P>
@@ -339,19 +692,74 @@ where
         parent: Option<&Object>,
         parent_scope: Option<&Scope<P>>,
     ) -> (UnboundedSender<ComponentMessage<C>>, Self) {
-        PartialComponentTask::new(props, parent, parent_scope).finalise()
+        PartialComponentTask::new(props, parent, Default::default(), parent_scope).finalise()
     }
 
-    fn run_job(&self, job: impl Future<Output = C::Message> + 'static) {
+    /// Spawn a deferred job, keeping hold of a handle to abort it with if the
+    /// component is unmounted before it completes.
+    fn run_job(&self, job: impl Future<Output = Option<C::Message>> + 'static) {
         let scope = self.scope.clone();
+        let (id, registration) = self.jobs.borrow_mut().register();
+        let jobs = Rc::downgrade(&self.jobs);
         MainContext::ref_thread_default().spawn_local(async move {
-            scope.send_message(job.await);
+            if let Ok(Some(msg)) = Abortable::new(job, registration).await {
+                scope.send_message(msg);
+            }
+            if let Some(jobs) = jobs.upgrade() {
+                jobs.borrow_mut().remove(id);
+            }
         })
     }
 
+    /// Carry out an `UpdateAction`, and report whether it asked for a render.
+    fn perform(&self, action: UpdateAction<C>, messages: &mut VecDeque<C::Message>) -> bool {
+        match action {
+            UpdateAction::None => false,
+            UpdateAction::Render => true,
+            UpdateAction::Defer(job) => {
+                self.run_job(job.map(Some));
+                false
+            }
+            UpdateAction::DeferCancellable(job, registration) => {
+                self.run_job(Abortable::new(job, registration).map(Result::ok));
+                false
+            }
+            UpdateAction::Message(msg) => {
+                messages.push_back(msg);
+                false
+            }
+            UpdateAction::Batch(actions) => {
+                let mut render = false;
+                for action in actions {
+                    render |= self.perform(action, messages);
+                }
+                render
+            }
+            UpdateAction::Quit => {
+                crate::quit();
+                false
+            }
+        }
+    }
+
     pub(crate) fn process(&mut self, ctx: &mut Context<'_>) -> Poll<()> {
         let mut render = false;
+        let mut updated = false;
+        let mut messages = VecDeque::new();
         loop {
+            // Messages sent with `UpdateAction::Message` skip the queue.
+            if let Some(msg) = messages.pop_front() {
+                debug!(
+                    "{} {}: {}",
+                    "UpdateAction::Message".green(),
+                    self.scope.name().magenta().bold(),
+                    format!("{:?}", msg).bright_white().bold()
+                );
+                let action = self.state.update(msg);
+                render |= self.perform(action, &mut messages);
+                updated = true;
+                continue;
+            }
             let next = Stream::poll_next(self.channel.as_mut(), ctx);
             trace!(
                 "{} {}",
@@ -360,24 +768,16 @@ where
             );
             match next {
                 Poll::Ready(Some(msg)) => match msg {
-                    ComponentMessage::Update(msg) => match self.state.update(msg) {
-                        UpdateAction::Defer(job) => {
-                            self.run_job(job);
-                        }
-                        UpdateAction::Render => {
-                            render = true;
-                        }
-                        UpdateAction::None => {}
-                    },
-                    ComponentMessage::Props(props) => match self.state.change(props) {
-                        UpdateAction::Defer(job) => {
-                            self.run_job(job);
-                        }
-                        UpdateAction::Render => {
-                            render = true;
-                        }
-                        UpdateAction::None => {}
-                    },
+                    ComponentMessage::Update(msg) => {
+                        let action = self.state.update(msg);
+                        render |= self.perform(action, &mut messages);
+                        updated = true;
+                    }
+                    ComponentMessage::Props(props) => {
+                        let action = self.state.change(props);
+                        render |= self.perform(action, &mut messages);
+                        updated = true;
+                    }
                     ComponentMessage::Mounted => {
                         debug!(
                             "{} {}",
@@ -385,10 +785,16 @@ where
                             self.scope.name().magenta().bold()
                         );
                         self.state.mounted();
+                        self.report_first_render();
                     }
                     ComponentMessage::Unmounted => {
+                        self.subscriptions.stop();
+                        self.jobs.borrow_mut().abort();
+                        // A subcomponent at the root of our view shares our
+                        // placement, which may be another component's by now,
+                        // so we leave it to destroy its own objects.
                         if let Some(state) = self.ui_state.take() {
-                            state.unmount();
+                            state.unmount_root(&[]);
                         }
                         self.state.unmounted();
                         debug!(
@@ -400,17 +806,29 @@ where
                     }
                 },
                 Poll::Pending if render => {
+                    self.report_first_render();
                     if let Some(ref mut ui_state) = self.ui_state {
                         // we patch
                         let new_view = self.state.view();
                         self.scope.mute();
                         if !ui_state.patch(&new_view, None, &self.scope) {
-                            unimplemented!(
-                                "{}: don't know how to propagate failed patch",
-                                self.scope.name()
+                            debug!(
+                                "{} {}",
+                                self.scope.name().magenta().bold(),
+                                "replacing root object".bright_yellow()
+                            );
+                            ui_state.replace(
+                                &new_view,
+                                self.parent.as_ref(),
+                                &self.placement,
+                                &self.scope,
                             );
                         }
+                        self.update_placement();
+                        self.update_stylesheet();
                         self.scope.unmute();
+                        self.state.rendered(false);
+                        self.update_subscriptions();
                         return Poll::Pending;
                     } else {
                         debug!(
@@ -429,13 +847,64 @@ where
                     );
                     return Poll::Ready(());
                 }
-                Poll::Pending => return Poll::Pending,
+                Poll::Pending => {
+                    self.report_first_render();
+                    if updated {
+                        self.update_subscriptions();
+                    }
+                    return Poll::Pending;
+                }
             }
         }
     }
 
     pub(crate) fn object(&self) -> Option<Object> {
-        self.ui_state.as_ref().map(|state| state.object().clone())
+        self.ui_state.as_ref().and_then(State::object)
+    }
+
+    /// Tell the component its initial view has been rendered, if we haven't
+    /// already.
+    ///
+    /// The initial view was built before the task was first polled, but we
+    /// wait until then to report it, so the scope isn't muted by a parent in
+    /// the middle of a render, and until any `Mounted` message waiting for us
+    /// has been handled, so a top level component hears about its first
+    /// render after it's been mounted.
+    fn report_first_render(&mut self) {
+        if self.first_render {
+            self.first_render = false;
+            self.state.rendered(true);
+        }
+    }
+
+    /// Record the objects the component's view is currently rendered as, and
+    /// whether it's a fragment, in its placement.
+    ///
+    /// A subcomponent at the root of the view shares our placement and keeps
+    /// it up to date itself.
+    fn update_placement(&self) {
+        match &self.ui_state {
+            Some(State::Component(_)) | None => {}
+            Some(ui_state) => {
+                *self.placement.objects.borrow_mut() = ui_state.objects();
+                self.placement.fragment.set(ui_state.is_fragment());
+            }
+        }
+    }
+
+    /// Give the objects the component's view is currently rendered as the
+    /// style class its stylesheet is scoped to.
+    fn update_stylesheet(&self) {
+        if let (Some(stylesheet), Some(ui_state)) = (&self.stylesheet, &self.ui_state) {
+            stylesheet.apply(&ui_state.objects());
+        }
+    }
+
+    /// Bring the running subscriptions in line with what the component wants,
+    /// when it's first built and after an update.
+    fn update_subscriptions(&mut self) {
+        self.subscriptions
+            .update(self.state.subscriptions(), &self.scope);
     }
 
     pub(crate) fn current_parent_scope() -> Scope<C> {
@@ -533,7 +1002,8 @@ where
                 current_object: self
                     .ui_state
                     .as_ref()
-                    .map(|state| state.object().downgrade()),
+                    .and_then(State::object)
+                    .map(|object| object.downgrade()),
             };
         });
         let polled = self.get_mut().process(ctx);
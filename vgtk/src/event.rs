@@ -1,83 +1,126 @@
 use glib::prelude::*;
+use glib::value::{FromValue, FromValueOptional};
 use glib::{Object, SignalHandlerId, Value};
-use std::cell::Cell;
-use std::cmp::Ordering;
 
-use std::sync::Arc;
+use std::marker::PhantomData;
+use std::rc::Rc;
 
-use crate::component::Component;
-use crate::scope::Scope;
+use crate::vnode::VHandlerSlot;
 
+/// The arguments to a signal connected by name.
+///
+/// Signal handlers in the [`gtk!`][gtk!] macro usually take the same arguments as the
+/// corresponding `connect_*` method, but you can also connect to any signal by giving its name as
+/// a string, as in `on "notify::visible-child"=|event| ...`. This works for detailed signals like
+/// `notify::`, for signals from your own GObject subclasses, and for any signals the Gtk-rs bindings
+/// don't have methods for. The handler receives an `Event` holding the object which emitted the
+/// signal and the signal's arguments as [`Value`][Value]s, which you can get at with
+/// [`arg()`][arg] and [`arg_some()`][arg_some]:
+///
+/// ```rust,no_run
+/// # use vgtk::{gtk, VNode, Component};
+/// # use vgtk::lib::gtk::{Stack, StackExt};
+/// # use vgtk::lib::glib::Cast;
+/// # #[derive(Clone, Debug)] enum Message { PageChanged(Option<String>) }
+/// # #[derive(Default)] struct Comp;
+/// # impl Component for Comp { type Message = Message; type Properties = (); fn view(&self) -> VNode<Self> {
+/// gtk! {
+///     <Stack on "notify::visible-child"=|event| {
+///         let stack: Stack = event.source.downcast().unwrap();
+///         Message::PageChanged(stack.get_visible_child_name().map(|name| name.to_string()))
+///     } />
+/// }
+/// # }}
+/// ```
+///
+/// The handler returns a message like any other, and can also return a value to GTK as an
+/// `Option<Value>`, as described under [`HandlerResult`][HandlerResult].
+///
+/// [gtk!]: macro.gtk.html
+/// [Value]: ../glib/value/struct.Value.html
+/// [arg]: #method.arg
+/// [arg_some]: #method.arg_some
+/// [HandlerResult]: trait.HandlerResult.html
+#[derive(Debug)]
 pub struct Event {
+    /// The object which emitted the signal.
     pub source: Object,
+    /// The signal's arguments, not including the source object.
     pub args: Vec<Value>,
 }
 
-pub struct SignalHandler<C: Component> {
-    id: String,
-    handler_id: Cell<Option<SignalHandlerId>>,
-    handler: Arc<dyn Fn(Event) -> C::Message>,
-}
-
-impl<C: Component> PartialEq for SignalHandler<C> {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+impl Event {
+    fn new(args: &[Value]) -> Self {
+        let source = args[0]
+            .get()
+            .ok()
+            .flatten()
+            .expect("event args[0] was not an Object");
+        Event {
+            source,
+            args: args[1..].to_owned(),
+        }
     }
-}
 
-impl<C: Component> Eq for SignalHandler<C> {}
-
-impl<C: Component> PartialOrd for SignalHandler<C> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// Get the argument at `index`, which may be `None` if the argument is a null pointer.
+    ///
+    /// Panics if there is no such argument, or if it isn't of type `T`.
+    pub fn arg<'a, T: FromValueOptional<'a>>(&'a self, index: usize) -> Option<T> {
+        self.value(index).get().unwrap_or_else(|error| {
+            panic!("event argument {} has the wrong type: {}", index, error)
+        })
     }
-}
 
-impl<C: Component> Ord for SignalHandler<C> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.id.cmp(&other.id)
+    /// Get the argument at `index`, for types which can't be null, like numbers and booleans.
+    ///
+    /// Panics if there is no such argument, or if it isn't of type `T`.
+    pub fn arg_some<'a, T: FromValue<'a>>(&'a self, index: usize) -> T {
+        self.value(index).get_some().unwrap_or_else(|error| {
+            panic!("event argument {} has the wrong type: {}", index, error)
+        })
     }
-}
 
-impl<C: Component + 'static> SignalHandler<C> {
-    pub fn new<F, S>(id: S, f: F) -> Self
-    where
-        F: Fn(Event) -> C::Message + 'static,
-        S: Into<String>,
-    {
-        let id = id.into();
-        SignalHandler {
-            id,
-            handler_id: Cell::new(None),
-            handler: Arc::new(f),
-        }
+    fn value(&self, index: usize) -> &Value {
+        self.args.get(index).unwrap_or_else(|| {
+            panic!(
+                "event argument {} requested, but the signal only has {}",
+                index,
+                self.args.len()
+            )
+        })
     }
+}
 
-    pub fn connect<'a, S: Into<&'a str>>(&self, signal: S, obj: &Object, scope: Scope<C>) {
-        let signal = signal.into();
-        let handler = self.handler.clone();
-
-        let f: Arc<dyn Fn(Event) -> C::Message + Send + Sync + 'static> =
-            unsafe { Arc::from_raw(Arc::into_raw(handler) as *mut _) };
-
-        self.handler_id.set(Some(
-            obj.connect(signal, false, move |args: &[Value]| {
-                let source: Object = args[0].get().expect("event args[0] was not an Object");
-                let event = Event {
-                    source,
-                    args: args[1..].to_owned(),
-                };
-                let msg = f(event);
-                scope.send_message(msg);
-                None
-            })
-            .unwrap_or_else(|_e| panic!("invalid signal {:?} on {}", signal, obj.get_type())),
-        ));
-    }
+/// Give a handler closure for a signal connected by name the type it needs.
+pub fn event_handler<F>(handler: F) -> F
+where
+    F: Fn(Event) -> Option<Value>,
+{
+    handler
+}
 
-    pub fn disconnect(&self, obj: &Object) {
-        if let Some(id) = self.handler_id.replace(None) {
-            obj.disconnect(id);
-        }
-    }
+/// Connect a signal by name to a closure which calls whatever handler is in
+/// the slot it's given.
+pub fn connect_event<F>(
+    object: &Object,
+    signal: &str,
+    slot: Rc<VHandlerSlot>,
+    handler_type: PhantomData<F>,
+) -> SignalHandlerId
+where
+    F: Fn(Event) -> Option<Value> + 'static,
+{
+    object
+        .connect_local(signal, false, move |args: &[Value]| {
+            let handler = slot.get(handler_type);
+            handler(Event::new(args))
+        })
+        .unwrap_or_else(|error| {
+            panic!(
+                "cannot connect signal {:?} on {}: {}",
+                signal,
+                object.get_type(),
+                error
+            )
+        })
 }
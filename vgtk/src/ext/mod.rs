@@ -164,6 +164,15 @@ pub trait WindowExtHelpers: GtkWindowExt {
     fn set_window_position(&self, window_position: WindowPosition) {
         self.set_property_window_position(window_position)
     }
+
+    fn get_child_titlebar<P: IsA<Widget>>(&self, _child: &P) -> bool {
+        // Always compare true, it's all taken care of in the `attach` module.
+        true
+    }
+
+    fn set_child_titlebar<P: IsA<Widget>>(&self, _child: &P, _titlebar: bool) {
+        // This is handled by the `attach` module. The setter is a no-op.
+    }
 }
 
 impl<A> WindowExtHelpers for A where A: GtkWindowExt {}
@@ -173,12 +182,12 @@ impl<A> WindowExtHelpers for A where A: GtkWindowExt {}
 /// [Box]: ../../gtk/struct.Box.html
 pub trait BoxExtHelpers: BoxExt {
     fn get_child_center_widget(&self, _child: &Object) -> bool {
-        // Always compare true, it's all taken care of in add_child().
+        // Always compare true, it's all taken care of in the `attach` module.
         true
     }
 
     fn set_child_center_widget(&self, _child: &Object, _center: bool) {
-        // This is handled by add_child() rules. The setter is a no-op.
+        // This is handled by the `attach` module. The setter is a no-op.
     }
 }
 
@@ -189,12 +198,12 @@ impl<A> BoxExtHelpers for A where A: BoxExt {}
 /// [HeaderBar]: ../../gtk/struct.HeaderBar.html
 pub trait HeaderBarExtHelpers: HeaderBarExt {
     fn get_child_custom_title<P: IsA<Widget>>(&self, _child: &P) -> bool {
-        // Always compare true, it's all taken care of in add_child().
+        // Always compare true, it's all taken care of in the `attach` module.
         true
     }
 
     fn set_child_custom_title<P: IsA<Widget>>(&self, _child: &P, _center: bool) {
-        // This is handled by add_child() rules. The setter is a no-op.
+        // This is handled by the `attach` module. The setter is a no-op.
     }
 }
 
@@ -227,17 +236,17 @@ impl<A> LabelExtHelpers for A where A: LabelExt {}
 /// [Notebook]: ../../gtk/struct.Notebook.html
 pub trait NotebookExtHelpers: NotebookExt {
     fn set_child_action_widget_start<P: IsA<Widget>>(&self, _child: &P, _val: bool) {
-        // This is handled by add_child() rules. The setter is a no-op.
+        // This is handled by the `attach` module. The setter is a no-op.
     }
     fn get_child_action_widget_start<P: IsA<Widget>>(&self, _child: &P) -> bool {
-        // Always compare true, it's all taken care of in add_child().
+        // Always compare true, it's all taken care of in the `attach` module.
         true
     }
     fn set_child_action_widget_end<P: IsA<Widget>>(&self, _child: &P, _val: bool) {
-        // This is handled by add_child() rules. The setter is a no-op.
+        // This is handled by the `attach` module. The setter is a no-op.
     }
     fn get_child_action_widget_end<P: IsA<Widget>>(&self, _child: &P) -> bool {
-        // Always compare true, it's all taken care of in add_child().
+        // Always compare true, it's all taken care of in the `attach` module.
         true
     }
 }
@@ -1,11 +1,14 @@
-use gobject_sys;
-use gtk_sys;
+#![allow(unsafe_code)]
 
 use glib::translate::{from_glib, from_glib_none, mut_override, ToGlib, ToGlibPtr};
 use glib::{glib_bool_error, BoolError, ParamFlags, ParamSpec, ToValue};
-use gtk::{Container, Widget};
+use gtk::prelude::*;
+use gtk::{
+    Container, CssProvider, Notebook, PackType, Widget, STYLE_PROVIDER_PRIORITY_APPLICATION,
+};
 
 use std::os::raw::c_char;
+use std::ptr;
 
 extern "C" {
     fn gtk_container_class_find_child_property(
@@ -14,7 +17,10 @@ extern "C" {
     ) -> *mut gobject_sys::GParamSpec;
 }
 
-pub fn find_child_property<'a, P: Into<&'a str>>(parent: &Container, prop: P) -> Option<ParamSpec> {
+pub(crate) fn find_child_property<'a, P: Into<&'a str>>(
+    parent: &Container,
+    prop: P,
+) -> Option<ParamSpec> {
     let prop = prop.into();
     unsafe {
         let obj: *const gtk_sys::GtkContainer = parent.to_glib_none().0;
@@ -29,7 +35,7 @@ pub fn find_child_property<'a, P: Into<&'a str>>(parent: &Container, prop: P) ->
     }
 }
 
-pub fn set_child_property<'a, P: Into<&'a str>>(
+pub(crate) fn set_child_property<'a, P: Into<&'a str>>(
     parent: &Container,
     child: &Widget,
     prop: P,
@@ -81,3 +87,29 @@ pub fn set_child_property<'a, P: Into<&'a str>>(
 
     Ok(())
 }
+
+/// Remove a `Notebook`'s action widget, which the bindings don't let us set to `None`.
+pub(crate) fn unset_action_widget(notebook: &Notebook, pack_type: PackType) {
+    unsafe {
+        gtk_sys::gtk_notebook_set_action_widget(
+            notebook.to_glib_none().0,
+            ptr::null_mut(),
+            pack_type.to_glib(),
+        )
+    }
+}
+
+/// Find the `CssProvider` holding a widget's inline `style`, adding a new one
+/// to its style context if it doesn't have one yet.
+pub(crate) fn inline_style_provider(widget: &Widget) -> CssProvider {
+    const KEY: &str = "vgtk-inline-style";
+    if let Some(provider) = unsafe { widget.get_data::<CssProvider>(KEY) } {
+        return provider.clone();
+    }
+    let provider = CssProvider::new();
+    widget
+        .get_style_context()
+        .add_provider(&provider, STYLE_PROVIDER_PRIORITY_APPLICATION);
+    unsafe { widget.set_data(KEY, provider.clone()) };
+    provider
+}
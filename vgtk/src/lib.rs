@@ -54,7 +54,7 @@
 //!         gtk! {
 //!             <Application::new_unwrap(None, ApplicationFlags::empty())>
 //!                 <Window border_width=20 on destroy=|_| Message::Exit>
-//!                     <HeaderBar title="inc!" show_close_button=true />
+//!                     <HeaderBar Window::titlebar=true title="inc!" show_close_button=true />
 //!                     <Box spacing=10 halign=Align::Center>
 //!                         <Label label=self.counter.to_string() />
 //!                         <Button label="inc!" image="add" always_show_image=true
@@ -125,7 +125,16 @@
 //! [`Defer`][UpdateAction::Defer] with a [`Future`][Future] in case you need to
 //! do some I/O or a similar asynchronous task - the [`Future`][Future] should resolve to a
 //! [`Component::Message`][Component::Message] which will be passed along to [`update`][Component::update]
-//! when the [`Future`][Future] resolves.
+//! when the [`Future`][Future] resolves. Deferred jobs are tied to the component which started them,
+//! and are cancelled if it's unmounted before they complete. If you need to cancel a job yourself,
+//! use [`UpdateAction::defer_cancellable()`][UpdateAction::defer_cancellable] to get a
+//! [`JobHandle`][JobHandle] for it.
+//!
+//! If you need to do more than one of these things, you can combine actions with
+//! [`UpdateAction::batch()`][UpdateAction::batch] or [`and_then()`][UpdateAction::and_then], as in
+//! `UpdateAction::Render.and_then(async { Message::Done })`. There are also actions for sending
+//! a message straight back to your [`update`][Component::update] function, and for quitting the
+//! application.
 //!
 //! ## Signal Handlers
 //!
@@ -140,8 +149,9 @@
 //! [`connect_clicked`][Button::connect_clicked] method, we see that it takes a single `&Self` argument,
 //! representing the button being clicked. In order to listen to this signal, we attach a closure
 //! with a similar function signature to the button using the `on` syntax. The closure always takes the
-//! same arguments as the `connect_*` callback, but instead of returning nothing it returns a message of
-//! the component's message type. This message will be passed to the component's
+//! same arguments as the `connect_*` callback, even if it doesn't use them, so a handler which doesn't
+//! care which button was clicked is written `|_|` rather than `||`. Instead of returning nothing, it
+//! returns a message of the component's message type. This message will be passed to the component's
 //! [`update`][Component::update] method by the framework.
 //!
 //! ```rust,no_run
@@ -159,6 +169,21 @@
 //! This will cause a `Message::ButtonWasClicked` message to be sent to your component's
 //! [`update`][Component::update] function when the user clicks the button.
 //!
+//! A signal is only connected once, when its widget is first built, but the handler it runs is
+//! always the closure from the most recent render, so any values it captures from your component's
+//! state are never stale.
+//!
+//! Some signals expect their handlers to return a value to GTK, like the [`Inhibit`][Inhibit]
+//! returned from a [`Window`][Window]'s `delete_event` handler to stop it from closing. Handlers for
+//! these can return a tuple of a message and the return value, as in
+//! `on delete_event=|_, _| (Message::AskToClose, Inhibit(true))`, and either half of it can be left
+//! out: see [`HandlerResult`][HandlerResult] for the details.
+//!
+//! You can also connect to a signal by its name, which is useful for detailed signals like
+//! `notify::visible-child` and signals the Gtk-rs bindings don't have `connect_*` methods for.
+//! Write the name as a string, as in `on "notify::visible-child"=|event| ...`, and the handler
+//! will receive an [`Event`][Event] holding the signal's arguments.
+//!
 //! Signal handlers can also be declared as `async`, which will cause the framework to wrap the handler
 //! in an `async {}` block and `await` the
 //! message result before passing it on to your update function. For instance, this very contrived
@@ -184,6 +209,16 @@
 //! # }}
 //! ```
 //!
+//! ## Subscriptions
+//!
+//! Some messages don't come from the user at all, but from timers, file monitors or other long
+//! running streams of events. Your component can list these in its
+//! [`subscriptions`][Component::subscriptions] method, which returns a set of
+//! [`Subscription`][Subscription]s, each of which sends messages to your component's
+//! [`update`][Component::update] function as events arrive. The framework calls it after every
+//! update and starts or stops subscriptions as the list changes, identifying them by their IDs, and
+//! stops them all when your component is unmounted.
+//!
 //! ## The `gtk!` Syntax
 //!
 //! The syntax for the [`gtk!`][vgtk::gtk!] macro is similar to [JSX], but with a number of necessary
@@ -220,13 +255,39 @@
 //! # }
 //! ```
 //!
-//! If a widget has a constructor that takes arguments, you can use that constructor in place
-//! of the element's tag name. This syntax should only be used in cases where a widget simply cannot be constructed
-//! using properties alone, because the differ isn't able to update arguments that may have changed
-//! in constructors once the widget has been instantiated. It should be reserved only for when it's
-//! absolutely necessary, such as when constructing an [`Application`][Application], which doesn't
-//! implement [`Buildable`][Buildable] and therefore can't be constructed in any way other than through
-//! a constructor method.
+//! Most containers have their children added with `Container::add`, but some need special
+//! treatment: a [`Window`][Window] puts a child with the `Window::titlebar=true` child property in
+//! its title bar, for instance, and an [`Application`][Application] takes windows and actions rather
+//! than widgets. How children are added, removed and reordered is decided by the
+//! [`ChildAttach`][ChildAttach] registered for the parent's type, and you can register your own for
+//! containers which need it.
+//!
+//! Objects are constructed directly from their type, and any [`prop:`](#properties-by-name)
+//! attributes naming construct properties are given to the object as it's being constructed. This
+//! means even construct-only properties, which can't be set once the object exists, can be set as
+//! attributes:
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode};
+//! # use vgtk::lib::{gtk::Application, gio::ApplicationFlags};
+//! # fn view() -> VNode<()> {
+//! gtk! {
+//!     <Application prop:application_id="camp.lol.inc" prop:flags=ApplicationFlags::empty() />
+//! }
+//! # }
+//! ```
+//!
+//! A construct-only property can't be changed after the object has been constructed, so if its
+//! value changes on a later render, it's ignored with a warning. If you need it to change, use
+//! `construct:` instead of `prop:`, as in `<SimpleAction construct:name=self.action_name />`.
+//! The property is then always given to the object as it's constructed, and if its value changes,
+//! the object is thrown away and built anew with the new value.
+//!
+//! If a widget has a constructor that takes arguments, you can also use that constructor in place
+//! of the element's tag name. This syntax should only be used in cases where a widget simply cannot
+//! be constructed using properties alone, because the differ isn't able to update arguments that
+//! may have changed in constructors once the widget has been instantiated. Changing them has no
+//! effect, unlike `construct:` properties.
 //!
 //! ```rust,no_run
 //! # use vgtk::{gtk, VNode, ext::ApplicationHelpers};
@@ -299,6 +360,262 @@
 //! # }
 //! ```
 //!
+//! ### Control Flow
+//!
+//! Most of the time, you don't need a code block to decide which children to render, because
+//! `if`, `match` and `for` can be used in place of a child widget, and work just like their Rust
+//! counterparts, except that their bodies contain child nodes rather than statements. A `match`
+//! arm is either a single node or a `{}` block of them, and an `if` or a `match` arm which doesn't
+//! render anything can be left empty.
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode};
+//! # use vgtk::lib::gtk::{Box, Button, ButtonExt, Label, LabelExt, Spinner};
+//! # enum State { Loading, Failed(String), Loaded(Vec<String>) }
+//! # fn view(state: &State, can_retry: bool) -> VNode<()> {
+//! gtk! {
+//!     <Box>
+//!         match state {
+//!             State::Loading => <Spinner prop:active=true />,
+//!             State::Failed(error) => {
+//!                 <Label label=error.clone() />
+//!                 if can_retry {
+//!                     <Button label="Retry" />
+//!                 }
+//!             }
+//!             State::Loaded(lines) => for line in lines {
+//!                 <Label label=line.clone() />
+//!             }
+//!         }
+//!     </Box>
+//! }
+//! # }
+//! ```
+//!
+//! A `for` loop can give each of its items a [key](#keys) by following the iterator with
+//! `; key=...`, as in `for item in items; key=item.id { ... }`. Its body must then be a single
+//! widget or subcomponent, which is given the key.
+//!
+//! ### Fragments
+//!
+//! A fragment, written as an empty tag `<>...</>`, groups several nodes together without a widget
+//! to hold them. Its children are added to the fragment's parent in its place, as if they'd been
+//! written there directly. This lets a code block, a helper function, or a subcomponent's
+//! [`view`][Component::view] contribute more than one widget to a container:
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode};
+//! # use vgtk::lib::gtk::{Button, ButtonExt, HeaderBar, HeaderBarExt};
+//! fn buttons() -> VNode<()> {
+//!     gtk! {
+//!         <>
+//!             <Button label="Open" />
+//!             <Button label="Save" />
+//!         </>
+//!     }
+//! }
+//!
+//! # fn view() -> VNode<()> {
+//! gtk! {
+//!     <HeaderBar title="Fragments">
+//!         { buttons() }
+//!         <Button label="Quit" />
+//!     </HeaderBar>
+//! }
+//! # }
+//! ```
+//!
+//! A top level component's view can't be a fragment, because there's no parent to add its
+//! children to.
+//!
+//! ### Live Properties
+//!
+//! When a component re-renders, the differ compares each attribute's value with the value it had
+//! on the previous render, and only calls the setter if it's changed. This is cheap, but it means
+//! the differ never finds out if a property has been changed behind its back, such as when the
+//! user types into an [`Entry`][Entry] or clicks a [`ToggleButton`][ToggleButton]. If you want
+//! such a property to be reset to the value from your `view` whenever the two disagree, mark it
+//! with `live:`, and the differ will compare against the widget's getter instead:
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode};
+//! # use vgtk::lib::gtk::{Entry, EntryExt};
+//! # fn view(text: &str) -> VNode<()> {
+//! gtk! {
+//!     <Entry live:text=text />
+//! }
+//! # }
+//! ```
+//!
+//! ### Two-Way Bindings
+//!
+//! An input widget usually needs both a property showing your model's value and a signal handler
+//! sending the user's changes back to you. A `bind:` attribute does both: give it the value and,
+//! after a `=>`, a function or enum variant which turns the widget's new value into a message.
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode, Component};
+//! # use vgtk::lib::gtk::{Box, Entry, EntryExt, Switch, SwitchExt, Scale, RangeExt};
+//! # #[derive(Clone, Debug)] enum Message { Name(String), Enabled(bool), Volume(f64) }
+//! # #[derive(Default)] struct Comp { name: String, enabled: bool, volume: f64 }
+//! # impl Component for Comp { type Message = Message; type Properties = (); fn view(&self) -> VNode<Self> {
+//! gtk! {
+//!     <Box>
+//!         <Entry bind:text=self.name.clone() => Message::Name />
+//!         <Switch bind:active=self.enabled => Message::Enabled />
+//!         <Scale bind:value=self.volume => Message::Volume />
+//!     </Box>
+//! }
+//! # }}
+//! ```
+//!
+//! The property is compared against the widget's current value, like a `live:` property, and the
+//! message is sent whenever the property's `notify::` signal fires, or a [`Range`][Range]'s
+//! `value-changed` signal for its `value`. Changes made by setting the property from your view
+//! aren't sent back to you, so a model update can't turn into a loop, and an [`Entry`][Entry]
+//! keeps its cursor where it was when its text is replaced.
+//!
+//! [Range]: ../gtk/struct.Range.html
+//!
+//! ### Style Classes And Inline Styles
+//!
+//! A widget's `class` attribute gives it a list of space separated style classes, and a `class:`
+//! attribute gives it the named class only while its value is true. Underscores in the class name
+//! are turned into dashes, so `class:destructive_action` adds the `destructive-action` class.
+//! The `style` attribute takes CSS declarations which apply to the widget itself, without a
+//! selector:
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode};
+//! # use vgtk::lib::gtk::{Box, Button, ButtonExt, Entry};
+//! # fn view(invalid: bool) -> VNode<()> {
+//! gtk! {
+//!     <Box class="linked" style="padding: 6px;">
+//!         <Entry class:error=invalid />
+//!         <Button label="Save" class="suggested-action" class:destructive_action=invalid />
+//!     </Box>
+//! }
+//! # }
+//! ```
+//!
+//! Classes are diffed like any other attribute: only the ones which have appeared or disappeared
+//! since the previous render are added to or removed from the widget's
+//! [`StyleContext`][StyleContext], and classes added by GTK or your own code are left alone. Each
+//! widget with a `style` gets its own [`CssProvider`][CssProvider], which is updated in place when
+//! the style changes.
+//!
+//! Note that because `style` is taken, a [`Toolbar`][Toolbar]'s style has to be set by name, as
+//! in `prop:toolbar_style=ToolbarStyle::Icons`.
+//!
+//! [StyleContext]: ../gtk/struct.StyleContext.html
+//! [CssProvider]: ../gtk/struct.CssProvider.html
+//! [Toolbar]: ../gtk/struct.Toolbar.html
+//!
+//! A component can also come with its own stylesheet, by implementing
+//! [`Component::stylesheet()`][Component::stylesheet]. Its rules only apply to the widgets in
+//! that component's view, so you can use plain selectors like `label` without worrying about
+//! styling the rest of the application.
+//!
+//! ### Properties By Name
+//!
+//! An attribute normally calls the widget's `set_` method of the same name, so only properties
+//! with a Rust setter can be used. Prefix an attribute with `prop:` to set the GObject property
+//! of that name through [`ObjectExt::set_property`][set_property] instead, which works for any
+//! property on any widget, including your own GObject subclasses. Underscores in the name are
+//! turned into dashes, so `prop:max_width_chars` sets the `max-width-chars` property. Child
+//! properties work the same way, with the parent's type in front of the name:
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode};
+//! # use vgtk::lib::gtk::{Box, Label, LabelExt};
+//! # fn view() -> VNode<()> {
+//! gtk! {
+//!     <Box>
+//!         <Label label="Hello" prop:max_width_chars=20 prop:Box::padding=5u32 />
+//!     </Box>
+//! }
+//! # }
+//! ```
+//!
+//! Because there's no setter to tell the compiler what the property's type is, it's checked when
+//! the property is set instead, and vgtk will panic if the widget doesn't have the property or if
+//! the value isn't of the property's type. Note that this means the value has to be of exactly
+//! the right type, like the `u32` above, where a setter method would have let Rust infer it.
+//!
+//! [set_property]: ../glib/object/trait.ObjectExt.html#tymethod.set_property
+//!
+//! ### Optional And Spread Attributes
+//!
+//! An attribute written with `?=` takes an `Option`, and is only set when the value is `Some`.
+//! When it's `None`, the attribute is left out, as if you hadn't written it. If a widget
+//! property or child property was set by a previous render, it's reset to its default value,
+//! as given by its GObject property definition, so the widget looks as it would have if the
+//! attribute had never been set. A subcomponent property gets the value from the `Default`
+//! for the subcomponent's `Properties`, as any property you don't write out does. This works for
+//! widget properties, child properties and subcomponent properties alike:
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode};
+//! # use vgtk::lib::gtk::{Button, ButtonExt, WidgetExt};
+//! # fn view(tooltip: Option<String>) -> VNode<()> {
+//! gtk! {
+//!     <Button label="Save" tooltip_text?=tooltip />
+//! }
+//! # }
+//! ```
+//!
+//! A `..` attribute gives an element a whole set of attributes at once, which is useful for a
+//! component wrapping a widget which wants to let its user set any of the widget's properties.
+//! On a widget, it takes an [`Attributes`][Attributes], which names its properties the way you'd
+//! write them as attributes, as in `<Button label="Save" ..self.common />`. On a subcomponent,
+//! it takes a value of the subcomponent's `Properties` type, or a reference to one. Either way,
+//! the attributes written out on the element take precedence over the ones in the spread, and
+//! an element can only have one `..` attribute. When a property leaves a widget's spread, it's
+//! reset to its default value like an optional attribute is.
+//!
+//! Resetting to the default only works for properties which GObject knows about. An attribute
+//! with a setter method but no GObject property by its name keeps its last value, and a warning
+//! is logged.
+//!
+//! ### Keys
+//!
+//! When the differ updates a widget's children, it matches each new child with the old
+//! child at the same position, and patches it if they're of the same type or rebuilds it if
+//! they're not. This works well for static layouts, but for a list of items where entries
+//! can be inserted, removed or moved around, it means every item after the change gets
+//! patched to look like its neighbour, or rebuilt entirely if the types don't line up.
+//!
+//! You can avoid this by giving each child a `key` attribute which uniquely identifies it
+//! among its siblings. Both widgets and subcomponents accept a `key`, which can be any value
+//! implementing [`Display`][Display]. Keyed children are matched up by their key instead of by
+//! their position, so only the children which were actually added or removed will be built or
+//! destroyed, and children which have moved will be moved in place, keeping their focus and
+//! other GTK state intact.
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode};
+//! # use vgtk::lib::gtk::{Label, LabelExt, ListBox};
+//! # struct Item { id: usize, name: String }
+//! # fn view(items: &[Item]) -> VNode<()> {
+//! gtk! {
+//!     <ListBox>
+//!         for item in items {
+//!             <Label key=item.id label=item.name.clone() />
+//!         }
+//!     </ListBox>
+//! }
+//! # }
+//! ```
+//!
+//! ### Widget References
+//!
+//! If you need to get at a widget directly, say to call [`grab_focus()`][grab_focus] on an
+//! [`Entry`][Entry] from your [`update`][Component::update] function, you can keep a
+//! [`WidgetRef`][WidgetRef] in your component and give it to the widget with the `ref`
+//! attribute, as in `<Entry ref=self.entry />`. The reference is filled in when the widget is
+//! built and emptied when it's destroyed. Subcomponents accept a `ref` too, which refers to the
+//! root widget of their view.
+//!
 //! ## Subcomponents
 //!
 //! Components are designed to be composable, so you can place one component inside
@@ -410,6 +727,47 @@
 //! parent component it lives within inside its type signature. It'll just work, with nary a
 //! profunctor in sight.
 //!
+//! A subcomponent can take child nodes, which it receives as a [`Children`][Children] property
+//! and places in its own view. These are handy for layout components like panels and page frames:
+//!
+//! ```rust,ignore
+//! <@Card title="Greetings">
+//!     <@Card::buttons>
+//!         <Button label="OK" on clicked=|_| ParentMessage::Dismiss />
+//!     </@Card::buttons>
+//!     <Label label="Hello Joe!" />
+//! </@Card>
+//! ```
+//!
+//! Here, the `Button` goes in `Card`'s `buttons` property, which we call a named slot, and the
+//! `Label` goes in its `children` property. See the documentation for [`Children`][Children]
+//! for how the `Card` component would render these.
+//!
+//! A subcomponent can also be the root of another component's view. This is handy for thin
+//! wrappers which do nothing but fill in some of another component's properties:
+//!
+//! ```rust,no_run
+//! # use vgtk::{gtk, VNode, Component, Callback};
+//! # #[derive(Clone, Debug, Default)]
+//! # pub struct MyButton {
+//! #     pub label: String,
+//! #     pub on_clicked: Callback<()>,
+//! # }
+//! # impl Component for MyButton {
+//! #     type Message = ();
+//! #     type Properties = Self;
+//! #     fn view(&self) -> VNode<Self> { todo!() }
+//! # }
+//! # #[derive(Default)] struct OkButton;
+//! # impl Component for OkButton { type Message = (); type Properties = ();
+//! fn view(&self) -> VNode<Self> {
+//!     gtk! {
+//!         <@MyButton label="OK" />
+//!     }
+//! }
+//! # }
+//! ```
+//!
 //! ## Logging
 //!
 //! `vgtk` uses the [`log`][log] crate for debug output. You'll need to provide your own logger for this;
@@ -456,44 +814,69 @@
 //! [Component::update]: trait.Component.html#method.update
 //! [Component::create]: trait.Component.html#method.create
 //! [Component::change]: trait.Component.html#method.change
+//! [Component::subscriptions]: trait.Component.html#method.subscriptions
+//! [Component::stylesheet]: trait.Component.html#method.stylesheet
 //! [Component::Message]: trait.Component.html#associatedtype.Message
 //! [Component::Properties]: trait.Component.html#associatedtype.Properties
 //! [Callback]: struct.Callback.html
+//! [Subscription]: struct.Subscription.html
+//! [HandlerResult]: trait.HandlerResult.html
+//! [Event]: struct.Event.html
+//! [Children]: struct.Children.html
+//! [WidgetRef]: struct.WidgetRef.html
+//! [grab_focus]: ../gtk/trait.WidgetExt.html#tymethod.grab_focus
 //! [UpdateAction]: enum.UpdateAction.html
 //! [UpdateAction::None]: enum.UpdateAction.html#variant.None
 //! [UpdateAction::Render]: enum.UpdateAction.html#variant.Render
 //! [UpdateAction::Defer]: enum.UpdateAction.html#variant.Defer
+//! [UpdateAction::defer_cancellable]: enum.UpdateAction.html#method.defer_cancellable
+//! [UpdateAction::batch]: enum.UpdateAction.html#method.batch
+//! [UpdateAction::and_then]: enum.UpdateAction.html#method.and_then
+//! [JobHandle]: struct.JobHandle.html
+//! [ChildAttach]: attach/trait.ChildAttach.html
 //! [Application]: ../gtk/struct.Application.html
-//! [Buildable]: ../gtk/struct.Buildable.html
 //! [Button]: ../gtk/struct.Button.html
 //! [Button::connect_clicked]: ../gtk/trait.ButtonExt.html#tymethod.connect_clicked
 //! [Button::set_label]: ../gtk/trait.ButtonExt.html#tymethod.set_label
 //! [Box]: ../gtk/struct.Box.html
 //! [Box::new]: ../gtk/struct.Box.html#method.new
 //! [Container]: ../gtk/struct.Container.html
+//! [Entry]: ../gtk/struct.Entry.html
 //! [MenuButton]: ../gtk/struct.MenuButton.html
 //! [MenuButtonExt]: ../gtk/trait.MenuButtonExt.html
+//! [ToggleButton]: ../gtk/struct.ToggleButton.html
 //! [WidgetExt]: ../gtk/trait.WidgetExt.html
 //! [Window]: ../gtk/struct.Window.html
+//! [Inhibit]: ../glib/signal/struct.Inhibit.html
 //! [Future]: https://doc.rust-lang.org/std/future/trait.Future.html
+//! [Display]: https://doc.rust-lang.org/std/fmt/trait.Display.html
+//! [Attributes]: struct.Attributes.html
 
 #![forbid(rust_2018_idioms)]
 #![deny(nonstandard_style, unsafe_code)]
 #![warn(unreachable_pub, missing_docs)]
 #![allow(clippy::needless_doctest_main)]
 
+pub mod attach;
+mod attributes;
 mod callback;
+mod children;
 mod component;
+mod event;
 pub mod ext;
+mod ffi;
 mod menu_builder;
 #[doc(hidden)]
 pub mod properties;
 #[doc(hidden)]
 pub mod scope;
+mod stylesheet;
+mod subscription;
 pub mod types;
 mod vdom;
 #[doc(hidden)]
 pub mod vnode;
+mod widget_ref;
 
 use proc_macro_hack::proc_macro_hack;
 
@@ -521,11 +904,16 @@ use log::debug;
 
 use crate::component::{ComponentMessage, ComponentTask, PartialComponentTask};
 
+pub use crate::attributes::Attributes;
 pub use crate::callback::Callback;
-pub use crate::component::{current_object, current_window, Component, UpdateAction};
+pub use crate::children::Children;
+pub use crate::component::{current_object, current_window, Component, JobHandle, UpdateAction};
+pub use crate::event::Event;
 pub use crate::menu_builder::{menu, MenuBuilder};
 pub use crate::scope::Scope;
-pub use crate::vnode::{VNode, VNodeIterator};
+pub use crate::subscription::{Subscription, Subscriptions};
+pub use crate::vnode::{HandlerResult, VNode, VNodeIterator};
+pub use crate::widget_ref::WidgetRef;
 
 /// Re-exports of GTK and its associated libraries.
 ///
@@ -610,7 +998,8 @@ pub fn run<C: 'static + Component>() -> i32 {
 /// [Scope]: struct.Scope.html
 pub fn start<C: 'static + Component>() -> (Application, Scope<C>) {
     gtk::init().expect("GTK failed to initialise");
-    let partial_task = PartialComponentTask::<C, ()>::new(Default::default(), None, None);
+    let partial_task =
+        PartialComponentTask::<C, ()>::new(Default::default(), None, Default::default(), None);
     let app: Application = partial_task.object().downcast().unwrap_or_else(|_| {
         panic!(
             "The top level object must be an Application, but {} was found.",
@@ -1,15 +1,28 @@
 //! Property conversion traits.
 
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::marker::PhantomData;
+use std::rc::Rc;
 
-use glib::{Cast, GString};
-use gtk::{IconSize, Image, ImageExt, Widget};
+use glib::translate::ToGlib;
+use glib::{Cast, GString, IsA, Object, ObjectExt, ObjectType, ParamFlags, ToValue};
+use gtk::{
+    Container, CssProviderExt, Editable, EditableExt, IconSize, Image, ImageExt, Range,
+    StyleContextExt, Widget, WidgetExt,
+};
+
+use log::warn;
+
+use crate::ffi;
+use crate::vnode::{VConstruct, VProperty};
 
 pub struct PropertyValue<'a, A, Get, Set>
 where
     A: PropertyValueCompare<'a, Get> + PropertyValueCoerce<'a, Set> + 'a,
 {
-    value: A,
+    value: Rc<A>,
     lifetime: PhantomData<&'a (Get, Set)>,
 }
 
@@ -19,7 +32,7 @@ where
 {
     pub fn new(value: A) -> Self {
         Self {
-            value,
+            value: Rc::new(value),
             lifetime: PhantomData,
         }
     }
@@ -31,6 +44,26 @@ where
     pub fn coerce(&'a self) -> Set {
         A::property_coerce(&self.value)
     }
+
+    /// Get the value for keeping around until the next render.
+    pub fn value(&self) -> Rc<dyn Any>
+    where
+        A: 'static,
+    {
+        self.value.clone()
+    }
+
+    /// Test whether the value differs from the one applied by a previous render.
+    ///
+    /// The getter is never called: it's only there to tell the compiler what
+    /// type the property has.
+    pub fn changed<F>(&self, previous: Option<&dyn Any>, _getter: F) -> bool
+    where
+        A: PartialEq + 'static,
+        F: FnOnce() -> Get,
+    {
+        changed_since(&*self.value, previous)
+    }
 }
 
 pub trait PropertyValueCompare<'a, A> {
@@ -157,21 +190,50 @@ impl<'a> PropertyValueCoerce<'a, Option<GString>> for String {
     }
 }
 
-impl<'a> PropertyValueCompare<'a, Option<Widget>> for Image {
-    fn property_compare(left: Option<Widget>, right: &Image) -> bool {
+/// An icon image, as created from an icon name for properties like
+/// [`Button::image`][image].
+///
+/// Two icons are equal if they have the same name and size.
+///
+/// [image]: ../../gtk/struct.Button.html#method.set_image
+#[derive(Clone, Debug)]
+pub struct Icon {
+    name: String,
+    size: IconSize,
+    image: Image,
+}
+
+impl Icon {
+    pub fn new(name: &str, size: IconSize) -> Self {
+        Icon {
+            name: name.to_string(),
+            size,
+            image: Image::from_icon_name(Some(name), size),
+        }
+    }
+}
+
+impl PartialEq for Icon {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.size == other.size
+    }
+}
+
+impl<'a> PropertyValueCompare<'a, Option<Widget>> for Icon {
+    fn property_compare(left: Option<Widget>, right: &Icon) -> bool {
         if let Some(left) = left {
             if let Some(left) = left.downcast_ref::<Image>() {
-                return left.get_property_icon_name() == right.get_property_icon_name()
-                    && left.get_property_icon_size() == right.get_property_icon_size();
+                return left.get_property_icon_name().as_deref() == Some(right.name.as_str())
+                    && left.get_property_icon_size() == right.size.to_glib();
             }
         }
         false
     }
 }
 
-impl<'a> PropertyValueCoerce<'a, Option<&'a Image>> for Image {
-    fn property_coerce(value: &'a Image) -> Option<&'a Image> {
-        Some(value)
+impl<'a> PropertyValueCoerce<'a, Option<&'a Image>> for Icon {
+    fn property_coerce(value: &'a Icon) -> Option<&'a Image> {
+        Some(&value.image)
     }
 }
 
@@ -225,21 +287,410 @@ where
     }
 }
 
-impl<'a, Get, Set> IntoPropertyValue<'a, Image, Get, Set> for (&'_ str, IconSize)
+impl<'a, Get, Set> IntoPropertyValue<'a, Icon, Get, Set> for (&'_ str, IconSize)
 where
-    Image: PropertyValueCompare<'a, Get> + PropertyValueCoerce<'a, Set>,
+    Icon: PropertyValueCompare<'a, Get> + PropertyValueCoerce<'a, Set>,
 {
-    fn into_property_value(self) -> PropertyValue<'a, Image, Get, Set> {
+    fn into_property_value(self) -> PropertyValue<'a, Icon, Get, Set> {
         let (name, size) = self;
-        PropertyValue::new(Image::from_icon_name(Some(name), size))
+        PropertyValue::new(Icon::new(name, size))
+    }
+}
+
+impl<'a, Get, Set> IntoPropertyValue<'a, Icon, Get, Set> for &'_ str
+where
+    Icon: PropertyValueCompare<'a, Get> + PropertyValueCoerce<'a, Set>,
+{
+    fn into_property_value(self) -> PropertyValue<'a, Icon, Get, Set> {
+        PropertyValue::new(Icon::new(self, IconSize::Button))
+    }
+}
+
+/// Test whether a value differs from the one applied by a previous render,
+/// counting a previous value of another type, or none at all, as different.
+fn changed_since<A: PartialEq + 'static>(value: &A, previous: Option<&dyn Any>) -> bool {
+    match previous.and_then(|previous| previous.downcast_ref::<A>()) {
+        Some(previous) => previous != value,
+        None => true,
+    }
+}
+
+/// Set a property through its GObject name, for `prop:` attributes.
+///
+/// The value is only set if it differs from the one applied by the previous
+/// render. Panics if the object has no such property, or if it can't hold the
+/// value.
+///
+/// Construct-only properties are given to the object when it's built, so
+/// they're only checked here. They can't be changed afterwards, so a change is
+/// logged and ignored.
+pub fn set_property_by_name<A>(object: &Object, name: &str, value: &A, previous: Option<&dyn Any>)
+where
+    A: ToValue + PartialEq + 'static,
+{
+    if !changed_since(value, previous) {
+        return;
+    }
+    let construct_only = object
+        .find_property(name)
+        .map(|pspec| pspec.get_flags().contains(ParamFlags::CONSTRUCT_ONLY))
+        == Some(true);
+    if construct_only {
+        if previous.is_none() {
+            panic!(
+                "cannot set construct-only property {:?} on a {} built with a constructor",
+                name,
+                object.get_type()
+            );
+        }
+        warn!(
+            "construct-only property {:?} on {} can't be changed after construction",
+            name,
+            object.get_type()
+        );
+        return;
+    }
+    if let Err(error) = object.set_property(name, value) {
+        panic!(
+            "cannot set property {:?} on {}: {}",
+            name,
+            object.get_type(),
+            error
+        );
     }
 }
 
-impl<'a, Get, Set> IntoPropertyValue<'a, Image, Get, Set> for &'_ str
+/// Give a `prop:` attribute's value to the object's constructor, in case it's
+/// a construct property.
+pub fn construct_by_name<A>(name: &'static str, value: Rc<A>) -> VConstruct
 where
-    Image: PropertyValueCompare<'a, Get> + PropertyValueCoerce<'a, Set>,
+    A: ToValue + 'static,
+{
+    VConstruct {
+        name,
+        value: Rc::new(move || value.to_value()),
+        rebuild: None,
+    }
+}
+
+/// Give a `construct:` attribute's value to the object's constructor, and
+/// have the object rebuilt whenever it changes.
+pub fn rebuild_by_name<A>(name: &'static str, value: Rc<A>) -> VConstruct
+where
+    A: ToValue + PartialEq + 'static,
+{
+    let previous_value = value.clone();
+    VConstruct {
+        name,
+        value: Rc::new(move || value.to_value()),
+        rebuild: Some(Rc::new(move |previous| {
+            changed_since(&*previous_value, Some(previous))
+        })),
+    }
+}
+
+/// Set a child property through its GObject name, for `prop:` attributes
+/// with a parent type, as in `prop:Box::padding=5`.
+///
+/// Panics like [`set_property_by_name`][set_property_by_name] does, and also
+/// if the parent isn't a `P`.
+///
+/// [set_property_by_name]: fn.set_property_by_name.html
+pub fn set_child_property_by_name<P, A>(
+    object: &Object,
+    parent: Option<&Object>,
+    name: &str,
+    value: &A,
+    previous: Option<&dyn Any>,
+) where
+    P: IsA<Container> + IsA<Object>,
+    A: ToValue + PartialEq + 'static,
 {
-    fn into_property_value(self) -> PropertyValue<'a, Image, Get, Set> {
-        PropertyValue::new(Image::from_icon_name(Some(self), IconSize::Button))
+    let parent = parent.expect("child attribute without a reachable parent");
+    let parent: &Container = parent
+        .downcast_ref::<P>()
+        .unwrap_or_else(|| {
+            panic!(
+                "downcast to {:?} failed on parent in property setter",
+                P::static_type()
+            )
+        })
+        .upcast_ref();
+    let child: &Widget = object
+        .downcast_ref()
+        .expect("child property set on an object which isn't a Widget");
+    if !changed_since(value, previous) {
+        return;
+    }
+    if let Err(error) = ffi::set_child_property(parent, child, name, value) {
+        panic!(
+            "cannot set child property {:?} on {} in {}: {}",
+            name,
+            child.get_type(),
+            parent.get_type(),
+            error
+        );
+    }
+}
+
+/// Reset a property to its default value, for optional and spread attributes
+/// which have gone away since the previous render.
+///
+/// Properties the object doesn't have by that name, and properties which can't
+/// be set after construction, are left as they are.
+pub fn reset_property_by_name(object: &Object, name: &str) {
+    let pspec = match object.find_property(name) {
+        Some(pspec) => pspec,
+        None => {
+            warn!(
+                "cannot reset property {:?} on {}: no such property",
+                name,
+                object.get_type()
+            );
+            return;
+        }
+    };
+    let flags = pspec.get_flags();
+    if !flags.contains(ParamFlags::WRITABLE) || flags.contains(ParamFlags::CONSTRUCT_ONLY) {
+        return;
+    }
+    if let Some(default) = pspec.get_default_value() {
+        if let Err(error) = object.set_property(name, &default) {
+            warn!(
+                "cannot reset property {:?} on {}: {}",
+                name,
+                object.get_type(),
+                error
+            );
+        }
+    }
+}
+
+/// Reset a child property to its default value, like
+/// [`reset_property_by_name`][reset_property_by_name] does for properties.
+///
+/// [reset_property_by_name]: fn.reset_property_by_name.html
+pub fn reset_child_property_by_name(object: &Object, parent: Option<&Object>, name: &str) {
+    let (parent, child) = match (
+        parent.and_then(|parent| parent.downcast_ref::<Container>()),
+        object.downcast_ref::<Widget>(),
+    ) {
+        (Some(parent), Some(child)) => (parent, child),
+        _ => return,
+    };
+    let default =
+        ffi::find_child_property(parent, name).and_then(|pspec| pspec.get_default_value());
+    if let Some(default) = default {
+        if let Err(error) = ffi::set_child_property(parent, child, name, &default) {
+            warn!(
+                "cannot reset child property {:?} on {} in {}: {}",
+                name,
+                child.get_type(),
+                parent.get_type(),
+                error
+            );
+        }
+    }
+}
+
+/// Add the space separated style classes in a `class` attribute to a list.
+pub fn add_classes<S: AsRef<str> + ?Sized>(classes: &mut Vec<String>, value: &S) {
+    classes.extend(value.as_ref().split_whitespace().map(ToString::to_string));
+}
+
+/// Build the property for a widget's style classes, from its `class` and
+/// `class:` attributes.
+///
+/// Only classes which have been added or removed since the previous render are
+/// changed, and classes added by anything else are left alone.
+pub fn class_property(classes: Vec<String>) -> VProperty {
+    let value = Rc::new(classes);
+    let classes = value.clone();
+    VProperty {
+        name: "class",
+        value,
+        set: Rc::new(move |object, _parent, previous| {
+            let previous = previous.and_then(|previous| previous.downcast_ref::<Vec<String>>());
+            let context = style_widget(object, "class").get_style_context();
+            let empty = Vec::new();
+            let previous = previous.unwrap_or(&empty);
+            for class in previous.iter().filter(|class| !classes.contains(class)) {
+                context.remove_class(class);
+            }
+            for class in classes.iter().filter(|class| !previous.contains(class)) {
+                context.add_class(class);
+            }
+        }),
+        construct: None,
+        unset: Some(Rc::new(|object, _parent, previous| {
+            if let Some(previous) = previous.downcast_ref::<Vec<String>>() {
+                let context = style_widget(object, "class").get_style_context();
+                for class in previous {
+                    context.remove_class(class);
+                }
+            }
+        })),
+    }
+}
+
+/// Build the property for a widget's `style` attribute, which holds CSS
+/// declarations for the widget itself, as in `style="margin: 5px;"`.
+///
+/// The declarations are loaded into a `CssProvider` belonging to the widget,
+/// which is updated in place when they change.
+pub fn style_property<S: AsRef<str> + ?Sized>(style: &S) -> VProperty {
+    let value = Rc::new(style.as_ref().to_string());
+    let style = value.clone();
+    VProperty {
+        name: "style",
+        value,
+        set: Rc::new(move |object, _parent, previous| {
+            if changed_since(&*style, previous) {
+                load_inline_style(style_widget(object, "style"), &style);
+            }
+        }),
+        construct: None,
+        unset: Some(Rc::new(|object, _parent, _previous| {
+            load_inline_style(style_widget(object, "style"), "");
+        })),
+    }
+}
+
+fn style_widget<'a>(object: &'a Object, attribute: &str) -> &'a Widget {
+    object.downcast_ref().unwrap_or_else(|| {
+        panic!(
+            "`{}` attribute used on a {}, which isn't a Widget",
+            attribute,
+            object.get_type()
+        )
+    })
+}
+
+fn load_inline_style(widget: &Widget, style: &str) {
+    let provider = ffi::inline_style_provider(widget);
+    let css = format!("* {{ {} }}", style);
+    if let Err(error) = provider.load_from_data(css.as_bytes()) {
+        warn!(
+            "invalid `style` attribute {:?} on {}: {}",
+            style,
+            widget.get_type(),
+            error
+        );
+    }
+}
+
+thread_local! {
+    /// The objects which are having a `bind:` property set, by address.
+    static SETTING_BOUND: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+/// Marks an object as having a `bind:` property set until it's dropped, which
+/// also happens if setting the property panics.
+struct SettingBound(Option<usize>);
+
+impl SettingBound {
+    fn new(object: &Object) -> Self {
+        let address = object.as_ptr() as usize;
+        if SETTING_BOUND.with(|objects| objects.borrow_mut().insert(address)) {
+            SettingBound(Some(address))
+        } else {
+            // Already marked further up the stack, which will unmark it.
+            SettingBound(None)
+        }
+    }
+}
+
+impl Drop for SettingBound {
+    fn drop(&mut self) {
+        if let Some(address) = self.0 {
+            let _ = SETTING_BOUND.try_with(|objects| objects.borrow_mut().remove(&address));
+        }
+    }
+}
+
+/// Set a `bind:` property, in such a way that the binding's handler won't
+/// report the change back as a message, and that an editable's cursor stays
+/// where the user left it.
+pub fn set_bound<O, F>(object: &O, set: F)
+where
+    O: IsA<Object>,
+    F: FnOnce(),
+{
+    let editable = object.dynamic_cast_ref::<Editable>();
+    let position = editable.map(EditableExt::get_position);
+    {
+        let _guard = SettingBound::new(object.upcast_ref());
+        set();
+    }
+    if let (Some(editable), Some(position)) = (editable, position) {
+        editable.set_position(position);
+    }
+}
+
+/// Test whether a `bind:` property is being set on an object, in which case
+/// any change notifications from it are our own doing and shouldn't be sent
+/// back as messages.
+pub fn is_setting_bound(object: &Object) -> bool {
+    SETTING_BOUND.with(|objects| objects.borrow().contains(&(object.as_ptr() as usize)))
+}
+
+/// Find the signal which tells a `bind:` attribute that its property has
+/// changed. This is the property's `notify::` signal, except for the `value`
+/// of a `Range`, which isn't a property but lives in its adjustment.
+pub fn bound_signal(object: &Object, name: &str) -> String {
+    if name == "value" && object.is::<Range>() {
+        "value-changed".to_string()
+    } else {
+        format!("notify::{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_setting_bound, reset_property_by_name, SettingBound};
+    use gio::{ActionExt, SimpleAction};
+    use glib::{Cast, Object, StaticType};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    fn object() -> Object {
+        Object::new(Object::static_type(), &[]).unwrap()
+    }
+
+    #[test]
+    fn only_marks_the_object_being_set() {
+        let (setting, other) = (object(), object());
+        {
+            let _guard = SettingBound::new(&setting);
+            assert!(is_setting_bound(&setting));
+            assert!(!is_setting_bound(&other));
+            {
+                let _nested = SettingBound::new(&setting);
+            }
+            assert!(is_setting_bound(&setting));
+        }
+        assert!(!is_setting_bound(&setting));
+    }
+
+    #[test]
+    fn unmarks_the_object_when_setting_panics() {
+        let setting = object();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let _guard = SettingBound::new(&setting);
+            panic!("setter failed");
+        }));
+        assert!(result.is_err());
+        assert!(!is_setting_bound(&setting));
+    }
+
+    #[test]
+    fn resets_properties_to_their_defaults() {
+        let action = SimpleAction::new("save", None);
+        action.set_enabled(false);
+        reset_property_by_name(action.upcast_ref(), "enabled");
+        assert!(action.get_enabled());
+        // Read-only and unknown properties are left alone.
+        reset_property_by_name(action.upcast_ref(), "name");
+        reset_property_by_name(action.upcast_ref(), "no-such-property");
+        assert_eq!(action.get_name().as_deref(), Some("save"));
     }
 }
@@ -0,0 +1,291 @@
+use gdk::Screen;
+use glib::{Cast, Object};
+use gtk::{CssProvider, CssProviderExt, StyleContext, StyleContextExt, Widget, WidgetExt};
+
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use log::warn;
+
+use crate::component::Component;
+
+thread_local! {
+    static INSTALLED: RefCell<HashMap<TypeId, (CssProvider, usize)>> = RefCell::new(HashMap::new());
+}
+
+/// A mounted component's claim on the stylesheet for its type.
+///
+/// The stylesheet's `CssProvider` is added to the default screen when the first
+/// component of the type acquires it, and removed again when the last one
+/// drops its claim.
+pub(crate) struct Stylesheet {
+    type_id: TypeId,
+    class: String,
+}
+
+impl Stylesheet {
+    /// Claim the stylesheet for the component type `C`, if it has one.
+    pub(crate) fn acquire<C: 'static + Component>() -> Option<Self> {
+        let css = C::stylesheet()?;
+        let screen = match Screen::get_default() {
+            Some(screen) => screen,
+            None => {
+                warn!(
+                    "no default screen to install the stylesheet for {} on",
+                    std::any::type_name::<C>()
+                );
+                return None;
+            }
+        };
+        let type_id = TypeId::of::<C>();
+        let class = scope_class(std::any::type_name::<C>());
+        INSTALLED.with(|installed| {
+            let mut installed = installed.borrow_mut();
+            if let Some((_, count)) = installed.get_mut(&type_id) {
+                *count += 1;
+                return Some(());
+            }
+            // A stylesheet which doesn't load isn't installed at all, rather
+            // than installed with whatever rules GTK managed to make sense of.
+            let provider = CssProvider::new();
+            if let Err(error) = provider.load_from_data(scope_css(css, &class).as_bytes()) {
+                warn!(
+                    "invalid stylesheet for {}: {}",
+                    std::any::type_name::<C>(),
+                    error
+                );
+                return None;
+            }
+            StyleContext::add_provider_for_screen(
+                &screen,
+                &provider,
+                gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+            );
+            installed.insert(type_id, (provider, 1));
+            Some(())
+        })?;
+        Some(Stylesheet { type_id, class })
+    }
+
+    /// Give the component's root objects the style class the stylesheet is
+    /// scoped to.
+    pub(crate) fn apply(&self, objects: &[Object]) {
+        for widget in objects
+            .iter()
+            .filter_map(|object| object.downcast_ref::<Widget>())
+        {
+            widget.get_style_context().add_class(&self.class);
+        }
+    }
+}
+
+impl Drop for Stylesheet {
+    fn drop(&mut self) {
+        INSTALLED.with(|installed| {
+            let mut installed = installed.borrow_mut();
+            if let Some((provider, count)) = installed.get_mut(&self.type_id) {
+                *count -= 1;
+                if *count == 0 {
+                    if let Some(screen) = Screen::get_default() {
+                        StyleContext::remove_provider_for_screen(&screen, provider);
+                    }
+                    installed.remove(&self.type_id);
+                }
+            }
+        });
+    }
+}
+
+/// Make a style class name unique to a component type from its type name.
+fn scope_class(type_name: &str) -> String {
+    let name: String = type_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("vgtk-{}", name)
+}
+
+/// Rewrite a stylesheet so that its rules only match widgets with the style
+/// class `class` and their descendants.
+///
+/// Each selector is turned into two: one matching it inside a widget with the
+/// class, and one with the class added to its first compound selector, so
+/// that it also matches the widget itself. A selector starting with a
+/// combinator, as in `> label`, is taken relative to the widget with the
+/// class. At-rules are left as they are.
+fn scope_css(css: &str, class: &str) -> String {
+    let css = strip_comments(css);
+    let mut out = String::with_capacity(css.len() * 2);
+    let mut rest = css.as_str();
+    while !rest.trim().is_empty() {
+        let (end, terminator) = match find_top_level(rest, |c| c == '{' || c == ';') {
+            Some(index) => (index, rest.as_bytes()[index]),
+            None => {
+                out.push_str(rest);
+                break;
+            }
+        };
+        let prelude = rest[..end].trim();
+        if terminator == b';' {
+            out.push_str(prelude);
+            out.push_str(";\n");
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let block_end = match find_block_end(&rest[end..]) {
+            Some(index) => end + index,
+            None => {
+                out.push_str(rest);
+                break;
+            }
+        };
+        if prelude.starts_with('@') {
+            out.push_str(prelude);
+        } else {
+            let selectors: Vec<String> = split_top_level(prelude, ',')
+                .into_iter()
+                .map(|selector| scope_selector(selector.trim(), class))
+                .collect();
+            out.push_str(&selectors.join(", "));
+        }
+        out.push(' ');
+        out.push_str(&rest[end..=block_end]);
+        out.push('\n');
+        rest = &rest[block_end + 1..];
+    }
+    out
+}
+
+fn scope_selector(selector: &str, class: &str) -> String {
+    let first_end = find_top_level(selector, |c| c.is_whitespace() || ">+~".contains(c))
+        .unwrap_or(selector.len());
+    if first_end == 0 {
+        format!(".{} {}", class, selector)
+    } else {
+        format!(
+            ".{} {}, {}.{}{}",
+            class,
+            selector,
+            &selector[..first_end],
+            class,
+            &selector[first_end..]
+        )
+    }
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Find the first character matching `pred` outside of any brackets or quotes.
+fn find_top_level(input: &str, pred: impl Fn(char) -> bool) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') | (None, '[') => depth += 1,
+            (None, ')') | (None, ']') => depth -= 1,
+            (None, c) if depth == 0 && pred(c) => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn split_top_level(input: &str, separator: char) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut rest = input;
+    while let Some(index) = find_top_level(rest, |c| c == separator) {
+        out.push(&rest[..index]);
+        rest = &rest[index + 1..];
+    }
+    out.push(rest);
+    out
+}
+
+/// Find the closing brace of the block starting at the beginning of `input`.
+fn find_block_end(input: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in input.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scope_class, scope_css, strip_comments};
+
+    #[test]
+    fn makes_class_names_from_type_names() {
+        assert_eq!(scope_class("my_app::Card"), "vgtk-my-app--Card");
+    }
+
+    #[test]
+    fn scopes_each_selector_in_a_list() {
+        assert_eq!(
+            scope_css("a, b { color: red; }", "c"),
+            ".c a, a.c, .c b, b.c { color: red; }\n"
+        );
+    }
+
+    #[test]
+    fn scopes_selectors_starting_with_a_combinator_to_the_widget() {
+        assert_eq!(
+            scope_css("> label { margin: 5px; }", "c"),
+            ".c > label { margin: 5px; }\n"
+        );
+    }
+
+    #[test]
+    fn keeps_brackets_in_the_first_compound_selector() {
+        assert_eq!(
+            scope_css(":not(.x) y { color: red; }", "c"),
+            ".c :not(.x) y, :not(.x).c y { color: red; }\n"
+        );
+        assert_eq!(
+            scope_css("entry[a=\"b, c\"] { color: red; }", "c"),
+            ".c entry[a=\"b, c\"], entry[a=\"b, c\"].c { color: red; }\n"
+        );
+    }
+
+    #[test]
+    fn leaves_at_rules_alone() {
+        assert_eq!(
+            scope_css("@define-color bg #fff;\nlabel { color: @bg; }", "c"),
+            "@define-color bg #fff;\n.c label, label.c { color: @bg; }\n"
+        );
+    }
+
+    #[test]
+    fn ignores_comments() {
+        assert_eq!(strip_comments("a /* b { } */ c /* d"), "a  c ");
+        assert_eq!(
+            scope_css("/* a, b */ label /* { */ { color: red; }", "c"),
+            ".c label, label.c { color: red; }\n"
+        );
+    }
+}
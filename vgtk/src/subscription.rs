@@ -0,0 +1,367 @@
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver},
+    future::{abortable, ready, AbortHandle},
+    stream::{empty, Stream},
+    task::{Context, Poll},
+    FutureExt, StreamExt,
+};
+use gio::{prelude::*, Cancellable, File, FileMonitor, FileMonitorEvent, FileMonitorFlags};
+use glib::MainContext;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Error, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::pin::Pin;
+use std::time::Duration;
+
+use log::{debug, warn};
+
+use crate::component::Component;
+use crate::scope::Scope;
+
+type MessageStream<Message> = Pin<Box<dyn Stream<Item = Message>>>;
+
+fn hash_id<A: Hash>(id: A) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A long lived source of messages for a [`Component`][Component].
+///
+/// A subscription is identified by an ID. When the subscriptions returned from
+/// [`Component::subscriptions()`][subscriptions] change, subscriptions with new IDs are started,
+/// and subscriptions whose IDs have gone away are stopped, while the rest are left running as
+/// they are. This also means a subscription keeps sending the messages it was started with,
+/// until you give it a different ID.
+///
+/// A subscription whose stream ends is finished, and isn't started again while its ID stays
+/// in the list. To start it again, leave it out of the list for an update, or give it a new ID.
+///
+/// [Component]: trait.Component.html
+/// [subscriptions]: trait.Component.html#method.subscriptions
+pub struct Subscription<Message> {
+    id: u64,
+    start: Box<dyn FnOnce() -> MessageStream<Message>>,
+}
+
+impl<Message: 'static> Subscription<Message> {
+    /// Subscribe to a [`Stream`][Stream] of messages.
+    ///
+    /// The `id` identifies the subscription, and can be anything [`Hash`][Hash]able. The `stream`
+    /// function is only called to construct the stream when the subscription starts.
+    ///
+    /// [Stream]: https://docs.rs/futures/latest/futures/stream/trait.Stream.html
+    /// [Hash]: https://doc.rust-lang.org/std/hash/trait.Hash.html
+    pub fn stream<Id, F, S>(id: Id, stream: F) -> Self
+    where
+        Id: Hash,
+        F: FnOnce() -> S + 'static,
+        S: Stream<Item = Message> + 'static,
+    {
+        Subscription {
+            id: hash_id(id),
+            start: Box::new(move || stream().boxed_local()),
+        }
+    }
+
+    /// Send a message at a regular interval.
+    ///
+    /// The subscription is identified by its `id` together with its `period`, so that
+    /// changing the period restarts the timer. The `message` function isn't part of the
+    /// identity: if it captures values which change, use them in the `id` too, or the timer
+    /// will keep sending messages built from the values it was started with.
+    pub fn interval<Id, F>(id: Id, period: Duration, message: F) -> Self
+    where
+        Id: Hash,
+        F: Fn() -> Message + 'static,
+    {
+        Subscription::stream(("vgtk::interval", hash_id(id), period), move || {
+            glib::interval_stream(period.as_millis() as u32).map(move |()| message())
+        })
+    }
+
+    /// Watch a file or directory for changes using a [`FileMonitor`][FileMonitor].
+    ///
+    /// The `message` function is called with the arguments to the monitor's `changed` signal,
+    /// and may return a message to send. The subscription is identified by the file's URI, and
+    /// not by the `message` function.
+    ///
+    /// [FileMonitor]: ../gio/struct.FileMonitor.html
+    pub fn file_monitor<F>(file: &File, message: F) -> Self
+    where
+        F: Fn(&File, Option<&File>, FileMonitorEvent) -> Option<Message> + 'static,
+    {
+        let file = file.clone();
+        Subscription::stream(
+            ("vgtk::file_monitor", file.get_uri().as_str()),
+            move || match file.monitor(FileMonitorFlags::NONE, None::<&Cancellable>) {
+                Ok(monitor) => FileMonitorStream::new(monitor, message).boxed_local(),
+                Err(error) => {
+                    warn!("unable to monitor {}: {}", file.get_uri(), error);
+                    empty().boxed_local()
+                }
+            },
+        )
+    }
+}
+
+impl<Message> Debug for Subscription<Message> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "Subscription({:x})", self.id)
+    }
+}
+
+/// The list of [`Subscription`][Subscription]s returned from
+/// [`Component::subscriptions()`][subscriptions].
+///
+/// You can construct it from a single subscription or an iterator of subscriptions using
+/// `into()` and `collect()`, or add them one at a time:
+///
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use vgtk::{Subscription, Subscriptions};
+/// # #[derive(Clone, Debug)] enum Message { Tick, Refresh }
+/// # fn subscriptions(running: bool) -> Subscriptions<Message> {
+/// let mut subscriptions = Subscriptions::new()
+///     .with(Subscription::interval("refresh", Duration::from_secs(60), || Message::Refresh));
+/// if running {
+///     subscriptions.push(Subscription::interval("tick", Duration::from_secs(1), || Message::Tick));
+/// }
+/// subscriptions
+/// # }
+/// ```
+///
+/// [Subscription]: struct.Subscription.html
+/// [subscriptions]: trait.Component.html#method.subscriptions
+pub struct Subscriptions<Message>(Vec<Subscription<Message>>);
+
+impl<Message> Subscriptions<Message> {
+    /// Construct an empty list of subscriptions.
+    pub fn new() -> Self {
+        Subscriptions(Vec::new())
+    }
+
+    /// Add a subscription to the list.
+    pub fn push(&mut self, subscription: Subscription<Message>) {
+        self.0.push(subscription);
+    }
+
+    /// Add a subscription to the list and return it.
+    pub fn with(mut self, subscription: Subscription<Message>) -> Self {
+        self.push(subscription);
+        self
+    }
+
+    /// Test whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<Message> Default for Subscriptions<Message> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Message> From<Subscription<Message>> for Subscriptions<Message> {
+    fn from(subscription: Subscription<Message>) -> Self {
+        Subscriptions(vec![subscription])
+    }
+}
+
+impl<Message> FromIterator<Subscription<Message>> for Subscriptions<Message> {
+    fn from_iter<I: IntoIterator<Item = Subscription<Message>>>(iter: I) -> Self {
+        Subscriptions(iter.into_iter().collect())
+    }
+}
+
+impl<Message> Debug for Subscriptions<Message> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.debug_list().entries(&self.0).finish()
+    }
+}
+
+/// The subscriptions a component currently has running.
+///
+/// Any subscriptions still running are stopped when this is dropped.
+pub(crate) struct RunningSubscriptions(HashMap<u64, AbortHandle>);
+
+impl RunningSubscriptions {
+    pub(crate) fn new() -> Self {
+        RunningSubscriptions(HashMap::new())
+    }
+
+    /// Start any subscriptions we don't have yet, and stop any we have which
+    /// aren't in the new list.
+    pub(crate) fn update<C: 'static + Component>(
+        &mut self,
+        subscriptions: Subscriptions<C::Message>,
+        scope: &Scope<C>,
+    ) {
+        let (mut running, new) = self.keep(subscriptions);
+        for subscription in new {
+            let id = subscription.id;
+            let scope = scope.clone();
+            let name = scope.name();
+            let (task, handle) = abortable((subscription.start)().for_each(move |msg| {
+                scope.send_message(msg);
+                ready(())
+            }));
+            MainContext::ref_thread_default().spawn_local(task.map(move |result| {
+                // An aborted subscription was stopped; any other has run out.
+                if result.is_ok() {
+                    debug!("{}: subscription {:x} has ended", name, id);
+                }
+            }));
+            running.insert(id, handle);
+        }
+        self.stop();
+        self.0 = running;
+    }
+
+    /// Take the subscriptions in the new list which are already running out of
+    /// `self`, and return them along with the ones which have to be started.
+    ///
+    /// Whatever's left in `self` is no longer wanted. A subscription which is in
+    /// the list more than once is only kept or started once.
+    fn keep<Message>(
+        &mut self,
+        subscriptions: Subscriptions<Message>,
+    ) -> (HashMap<u64, AbortHandle>, Vec<Subscription<Message>>) {
+        let mut seen = HashSet::new();
+        let mut running = HashMap::new();
+        let mut new = Vec::new();
+        for subscription in subscriptions.0 {
+            if !seen.insert(subscription.id) {
+                continue;
+            }
+            match self.0.remove(&subscription.id) {
+                Some(handle) => {
+                    running.insert(subscription.id, handle);
+                }
+                None => new.push(subscription),
+            }
+        }
+        (running, new)
+    }
+
+    pub(crate) fn stop(&mut self) {
+        for (_, handle) in self.0.drain() {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for RunningSubscriptions {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// A stream of messages from a `FileMonitor`, which keeps the monitor alive.
+struct FileMonitorStream<Message> {
+    monitor: FileMonitor,
+    receiver: UnboundedReceiver<Message>,
+}
+
+impl<Message: 'static> FileMonitorStream<Message> {
+    fn new<F>(monitor: FileMonitor, message: F) -> Self
+    where
+        F: Fn(&File, Option<&File>, FileMonitorEvent) -> Option<Message> + 'static,
+    {
+        let (sender, receiver) = unbounded();
+        monitor.connect_changed(move |_, file, other_file, event| {
+            if let Some(msg) = message(file, other_file, event) {
+                let _ = sender.unbounded_send(msg);
+            }
+        });
+        FileMonitorStream { monitor, receiver }
+    }
+}
+
+impl<Message> Stream for FileMonitorStream<Message> {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<Message>> {
+        self.receiver.poll_next_unpin(ctx)
+    }
+}
+
+impl<Message> Drop for FileMonitorStream<Message> {
+    fn drop(&mut self) {
+        self.monitor.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running(ids: &[&str]) -> RunningSubscriptions {
+        RunningSubscriptions(
+            ids.iter()
+                .map(|id| (hash_id(id), abortable(ready(())).1))
+                .collect(),
+        )
+    }
+
+    fn subscriptions(ids: &[&str]) -> Subscriptions<()> {
+        ids.iter()
+            .map(|id| Subscription::stream(*id, empty))
+            .collect()
+    }
+
+    fn ids(ids: &[&str]) -> HashSet<u64> {
+        ids.iter().map(hash_id).collect()
+    }
+
+    #[test]
+    fn interval_is_identified_by_id_and_period() {
+        let second = Duration::from_secs(1);
+        let id = |subscription: Subscription<u32>| subscription.id;
+        assert_eq!(
+            id(Subscription::interval("tick", second, || 1)),
+            id(Subscription::interval("tick", second, || 2))
+        );
+        assert_ne!(
+            id(Subscription::interval("tick", second, || 1)),
+            id(Subscription::interval("tock", second, || 1))
+        );
+        assert_ne!(
+            id(Subscription::interval("tick", second, || 1)),
+            id(Subscription::interval("tick", second * 2, || 1))
+        );
+        assert_ne!(
+            id(Subscription::interval("tick", second, || 1)),
+            id(Subscription::stream("tick", empty))
+        );
+    }
+
+    #[test]
+    fn keeps_running_subscriptions_and_starts_new_ones() {
+        let mut current = running(&["a", "b"]);
+        let (kept, new) = current.keep(subscriptions(&["b", "c"]));
+        assert_eq!(kept.keys().copied().collect::<HashSet<_>>(), ids(&["b"]));
+        assert_eq!(
+            new.iter().map(|sub| sub.id).collect::<HashSet<_>>(),
+            ids(&["c"])
+        );
+        assert_eq!(
+            current.0.keys().copied().collect::<HashSet<_>>(),
+            ids(&["a"])
+        );
+    }
+
+    #[test]
+    fn starts_repeated_subscriptions_once() {
+        let mut current = running(&["a"]);
+        let (kept, new) = current.keep(subscriptions(&["a", "a", "b", "b"]));
+        assert_eq!(kept.len(), 1);
+        assert_eq!(new.len(), 1);
+        assert!(current.0.is_empty());
+    }
+}
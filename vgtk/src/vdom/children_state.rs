@@ -0,0 +1,64 @@
+use glib::Object;
+
+use std::rc::Rc;
+
+use super::fragment_state::FragmentState;
+use super::Placement;
+use crate::children::{AnyChildNodes, ChildNodes};
+use crate::component::Component;
+
+/// The state of a list of `Children` rendered by a subcomponent.
+///
+/// The nodes belong to the component which passed them in, not the one
+/// rendering them, so the state is kept behind this trait to hide its type.
+pub(crate) trait AnyChildrenState {
+    /// Patch the state with a new list of nodes.
+    ///
+    /// Returns false if they came from a different type of component, and the
+    /// state has to be rebuilt.
+    fn patch(&mut self, nodes: Rc<dyn AnyChildNodes>) -> bool;
+    fn objects(&self) -> Vec<Object>;
+    fn set_anchor(&self, anchor: Option<&Object>);
+    fn unmount(self: Box<Self>);
+}
+
+pub(crate) struct ChildrenState<Model: Component> {
+    nodes: Rc<ChildNodes<Model>>,
+    state: FragmentState<Model>,
+}
+
+impl<Model: 'static + Component> ChildrenState<Model> {
+    pub(crate) fn build(nodes: Rc<ChildNodes<Model>>, parent: Option<&Object>) -> Self {
+        let placement = Rc::new(Placement::default());
+        let state = FragmentState::build(&nodes.nodes, parent, placement, &nodes.scope());
+        ChildrenState { nodes, state }
+    }
+}
+
+impl<Model: 'static + Component> AnyChildrenState for ChildrenState<Model> {
+    fn patch(&mut self, nodes: Rc<dyn AnyChildNodes>) -> bool {
+        match nodes.into_any().downcast::<ChildNodes<Model>>() {
+            Err(_) => false,
+            // The subcomponent has re-rendered, but the children haven't
+            // changed since we last saw them.
+            Ok(nodes) if Rc::ptr_eq(&nodes, &self.nodes) => true,
+            Ok(nodes) => {
+                let patched = self.state.patch(&nodes.nodes, &nodes.scope());
+                self.nodes = nodes;
+                patched
+            }
+        }
+    }
+
+    fn objects(&self) -> Vec<Object> {
+        self.state.objects()
+    }
+
+    fn set_anchor(&self, anchor: Option<&Object>) {
+        self.state.set_anchor(anchor)
+    }
+
+    fn unmount(self: Box<Self>) {
+        self.state.unmount()
+    }
+}
@@ -5,11 +5,14 @@ use gtk::{prelude::*, Widget};
 
 use std::any::TypeId;
 use std::marker::PhantomData;
+use std::rc::Rc;
 
-use crate::component::{Component, ComponentMessage, ComponentTask};
+use super::Placement;
+use crate::component::{Component, ComponentMessage, PartialComponentTask};
 use crate::scope::Scope;
 use crate::vnode::component::AnyProps;
-use crate::vnode::{VComponent, VProperty};
+use crate::vnode::{VComponent, VProperty, VRef};
+use crate::widget_ref::RefTarget;
 
 trait PropertiesReceiver {
     fn update(&mut self, props: &AnyProps);
@@ -18,25 +21,45 @@ trait PropertiesReceiver {
 
 pub struct ComponentState<Model: Component> {
     parent: PhantomData<Model>,
-    pub(crate) object: Object,
-    model_type: TypeId,
+    pub(crate) placement: Rc<Placement>,
+    pub(crate) key: Option<String>,
+    reference: Option<VRef>,
+    pub(crate) model_type: TypeId,
     state: Box<dyn PropertiesReceiver>,
+    finalise: Option<Box<dyn FnOnce()>>,
 }
 
 impl<Model: 'static + Component> ComponentState<Model> {
-    pub fn build<Child: 'static + Component>(
+    // This function builds the component's root object, but not its children.
+    // You must call `build_children()` to finalise construction.
+    pub fn build_root<Child: 'static + Component>(
         props: &AnyProps,
         parent: Option<&Object>,
-        child_props: &[VProperty],
+        placement: Rc<Placement>,
         scope: &Scope<Model>,
     ) -> Self {
-        let (sub_state, object) =
-            SubcomponentState::<Child>::new(props, parent, child_props, scope);
+        let props: Child::Properties = props.unwrap();
+        let partial_task = PartialComponentTask::new(props, parent, placement.clone(), Some(scope));
+        let sub_state = SubcomponentState::<Child> {
+            channel: partial_task.channel(),
+        };
         ComponentState {
             parent: PhantomData,
-            object,
+            placement,
+            key: None,
+            reference: None,
             model_type: TypeId::of::<Child>(),
             state: Box::new(sub_state),
+            finalise: Some(Box::new(move || {
+                let (_, task) = partial_task.finalise();
+                MainContext::ref_thread_default().spawn_local(task);
+            })),
+        }
+    }
+
+    pub fn build_children(&mut self) {
+        if let Some(finalise) = self.finalise.take() {
+            finalise();
         }
     }
 
@@ -44,13 +67,18 @@ impl<Model: 'static + Component> ComponentState<Model> {
         &mut self,
         spec: &VComponent<Model>,
         parent: Option<&Object>,
-        _scope: &Scope<Model>,
+        scope: &Scope<Model>,
     ) -> bool {
         if self.model_type == spec.model_type {
-            // Components have same type; update props
-            for prop in &spec.child_props {
-                (prop.set)(self.object.upcast_ref(), parent, false);
+            // Components have same type; update props. A component at the
+            // root of another's view has no parent here, and leaves its child
+            // properties to whoever placed that component.
+            if let Some(parent) = parent {
+                let objects = self.placement.objects.borrow().clone();
+                self.apply_child_props(&spec.child_props, &objects, parent);
             }
+            self.set_reference(spec.reference.as_ref());
+            spec.bind_children(scope);
             self.state.update(&spec.props);
             true
         } else {
@@ -60,12 +88,42 @@ impl<Model: 'static + Component> ComponentState<Model> {
         }
     }
 
+    /// Point the component's reference, if it has one, at its placement.
+    pub(crate) fn set_reference(&mut self, reference: Option<&VRef>) {
+        let target = RefTarget::Component(Rc::downgrade(&self.placement));
+        VRef::update(&mut self.reference, reference, target);
+    }
+
+    pub(crate) fn apply_child_props(
+        &self,
+        props: &[VProperty],
+        objects: &[Object],
+        parent: &Object,
+    ) {
+        self.placement
+            .child_props
+            .borrow_mut()
+            .apply(props, objects, Some(parent));
+    }
+
     pub fn unmount(self) {
+        let objects = self.placement.objects.borrow().clone();
+        self.unmount_objects(&objects);
+    }
+
+    /// Unmount the component, destroying `objects` rather than the objects
+    /// currently in its placement.
+    pub(crate) fn unmount_objects(self, objects: &[Object]) {
         self.state.unmounting();
-        if let Ok(widget) = self.object.downcast::<Widget>() {
-            #[allow(unsafe_code)]
-            unsafe {
-                widget.destroy();
+        if let Some(reference) = &self.reference {
+            reference.detach(&RefTarget::Component(Rc::downgrade(&self.placement)));
+        }
+        for object in objects {
+            if let Some(widget) = object.downcast_ref::<Widget>() {
+                #[allow(unsafe_code)]
+                unsafe {
+                    widget.destroy();
+                }
             }
         }
     }
@@ -75,24 +133,6 @@ pub(crate) struct SubcomponentState<Model: Component> {
     channel: UnboundedSender<ComponentMessage<Model>>,
 }
 
-impl<Model: 'static + Component> SubcomponentState<Model> {
-    fn new<P: 'static + Component>(
-        props: &AnyProps,
-        parent: Option<&Object>,
-        child_props: &[VProperty],
-        parent_scope: &Scope<P>,
-    ) -> (Self, Object) {
-        let props: Model::Properties = props.unwrap();
-        let (channel, task) = ComponentTask::new(props, parent, Some(parent_scope));
-        let object = task.object().unwrap();
-        for prop in child_props {
-            (prop.set)(object.upcast_ref(), parent, true);
-        }
-        MainContext::ref_thread_default().spawn_local(task);
-        (SubcomponentState { channel }, object)
-    }
-}
-
 impl<Model: 'static + Component> PropertiesReceiver for SubcomponentState<Model> {
     fn update(&mut self, raw_props: &AnyProps) {
         let props = raw_props.unwrap();
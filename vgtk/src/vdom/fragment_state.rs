@@ -0,0 +1,96 @@
+use glib::Object;
+use gtk::{prelude::*, Container};
+
+use std::rc::Rc;
+
+use super::gtk_state::{attach_child, child_position, patch_children, set_anchors};
+use super::{Placement, State};
+use crate::component::Component;
+use crate::scope::Scope;
+use crate::vnode::VNode;
+
+/// The state of a fragment which is the root of a component's view.
+///
+/// Fragments anywhere else are flattened into their parent's list of children
+/// and don't have a state of their own. A component's view is rendered by its
+/// own task, though, so a fragment at its root has to look after its parent's
+/// children itself.
+///
+/// The placement is the component's, if the fragment is the root of its view,
+/// and the fragment's own otherwise.
+pub(crate) struct FragmentState<Model: Component> {
+    parent: Object,
+    placement: Rc<Placement>,
+    children: Vec<State<Model>>,
+}
+
+impl<Model: 'static + Component> FragmentState<Model> {
+    pub(crate) fn build(
+        specs: &[VNode<Model>],
+        parent: Option<&Object>,
+        placement: Rc<Placement>,
+        scope: &Scope<Model>,
+    ) -> Self {
+        let parent = parent
+            .expect("a fragment can't be the root of a top level component")
+            .clone();
+        let specs = VNode::flatten(specs);
+        let total = specs.len();
+        let mut children = Vec::with_capacity(total);
+        for (index, spec) in specs.into_iter().enumerate() {
+            let mut child = State::build(spec, Some(&parent), scope);
+            attach_child(&parent, index, total, spec, &mut child);
+            children.push(child);
+        }
+        set_anchors(&children, placement.anchor.borrow().as_ref());
+        FragmentState {
+            parent,
+            placement,
+            children,
+        }
+    }
+
+    pub(crate) fn patch(&mut self, specs: &[VNode<Model>], scope: &Scope<Model>) -> bool {
+        // Our children are somewhere among the parent's children, so we work
+        // out where from the position of the first one, or if we don't have
+        // any, from the position of the object before us. Should that have
+        // gone missing, new children just go at the end.
+        let anchor = self.placement.anchor.borrow().clone();
+        let offset = self.parent.downcast_ref::<Container>().map_or(0, |parent| {
+            let siblings = parent.get_children();
+            match (self.objects().first(), &anchor) {
+                (Some(first), _) => child_position(parent, &siblings, first),
+                (None, Some(anchor)) => {
+                    child_position(parent, &siblings, anchor).map(|index| index + 1)
+                }
+                (None, None) => Some(0),
+            }
+            .unwrap_or(siblings.len())
+        });
+        patch_children(
+            &self.parent,
+            &mut self.children,
+            &VNode::flatten(specs),
+            offset,
+            true,
+            scope,
+        );
+        set_anchors(&self.children, anchor.as_ref());
+        true
+    }
+
+    pub(crate) fn set_anchor(&self, anchor: Option<&Object>) {
+        *self.placement.anchor.borrow_mut() = anchor.cloned();
+        set_anchors(&self.children, anchor);
+    }
+
+    pub(crate) fn objects(&self) -> Vec<Object> {
+        self.children.iter().flat_map(State::objects).collect()
+    }
+
+    pub(crate) fn unmount(self) {
+        for child in self.children {
+            child.unmount();
+        }
+    }
+}
@@ -1,274 +1,336 @@
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::slice::from_ref;
 
-use gio::{Action, ActionExt, ActionMapExt};
-use glib::{prelude::*, Object, SignalHandlerId};
-use gtk::{
-    self, prelude::*, Application, ApplicationWindow, Bin, Box as GtkBox, Builder, Container,
-    Dialog, Grid, GridExt, HeaderBar, Menu, MenuButton, MenuItem, Notebook, ShortcutsWindow,
-    Widget, Window,
-};
+use glib::{prelude::*, Object, ObjectClass, ParamFlags, SignalHandlerId, Type, Value};
+use gtk::{prelude::*, Builder, Container, Widget};
 
-use super::State;
+use super::{Placement, PropertyValues, State};
+use crate::attach::{add_child, direct_child, remove_child, reorder_child};
 use crate::component::Component;
 use crate::scope::Scope;
-use crate::vnode::{VHandler, VNode, VObject, VProperty};
+use crate::vnode::{VHandler, VHandlerSlot, VNode, VObject, VRef};
+use crate::widget_ref::RefTarget;
 
 pub(crate) struct GtkState<Model: Component> {
     pub(crate) object: Object,
-    handlers: HashMap<(&'static str, &'static str), SignalHandlerId>,
+    pub(crate) key: Option<String>,
+    pub(crate) child_props: PropertyValues,
+    reference: Option<VRef>,
+    properties: PropertyValues,
+    handlers: HashMap<(&'static str, &'static str), (SignalHandlerId, Rc<VHandlerSlot>)>,
     children: Vec<State<Model>>,
 }
 
-fn build_obj<A: IsA<Object>, Model: Component>(spec: &VObject<Model>) -> A {
+/// Find the properties in a spec which are best given to the object's
+/// constructor, along with their GObject names and values.
+fn construct_properties<Model: Component>(
+    spec: &VObject<Model>,
+) -> Vec<(&'static str, &'static str, Value)> {
+    let class = match ObjectClass::from_type(spec.object_type) {
+        Some(class) => class,
+        None => return Vec::new(),
+    };
+    spec.properties
+        .iter()
+        .filter_map(|prop| {
+            let construct = prop.construct.as_ref()?;
+            let flags = class.find_property(construct.name)?.get_flags();
+            if flags.contains(ParamFlags::WRITABLE)
+                && (construct.rebuild.is_some()
+                    || flags.intersects(ParamFlags::CONSTRUCT | ParamFlags::CONSTRUCT_ONLY))
+            {
+                Some((prop.name, construct.name, (construct.value)()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Construct an object through `GtkBuilder`, for types `Object::new` can't
+/// handle.
+fn build_with_builder(class: Type) -> Object {
+    let mut ui = String::new();
+    ui += &format!("<interface><object class=\"{}\"", class);
+    ui += "/></interface>";
+
+    let builder = Builder::from_string(&ui);
+    let objects = builder.get_objects();
+    objects
+        .last()
+        .unwrap_or_else(|| panic!("unknown class {}", class))
+        .clone()
+}
+
+/// Construct the object for a spec, returning it along with the names of the
+/// properties it was given at construction, which don't need setting again.
+fn build_obj<A: IsA<Object>, Model: Component>(spec: &VObject<Model>) -> (A, Vec<&'static str>) {
     let class = spec.object_type;
-    let obj = if let Some(ref cons) = spec.constructor {
-        cons()
+    let (obj, constructed) = if let Some(ref cons) = spec.constructor {
+        (cons(), Vec::new())
     } else {
-        let mut ui = String::new();
-        ui += &format!("<interface><object class=\"{}\"", class);
-        ui += "/></interface>";
-
-        let builder = Builder::from_string(&ui);
-        let objects = builder.get_objects();
-        objects
-            .last()
-            .unwrap_or_else(|| panic!("unknown class {}", class))
-            .clone()
+        let construct = construct_properties(spec);
+        let values: Vec<(&str, &dyn ToValue)> = construct
+            .iter()
+            .map(|(_, name, value)| (*name, value as &dyn ToValue))
+            .collect();
+        match Object::new(class, &values) {
+            Ok(obj) => (obj, construct.iter().map(|(name, _, _)| *name).collect()),
+            Err(_) if construct.is_empty() => (build_with_builder(class), Vec::new()),
+            Err(error) => panic!("build_obj: cannot construct {}: {}", class, error),
+        }
     };
-    obj.downcast::<A>()
-        .unwrap_or_else(|_| panic!("build_obj: cannot cast {} to {}", class, A::static_type()))
+    let obj = obj
+        .downcast::<A>()
+        .unwrap_or_else(|_| panic!("build_obj: cannot cast {} to {}", class, A::static_type()));
+    (obj, constructed)
+}
+
+/// Connect a signal handler through a slot holding its current closure.
+fn connect<Model: Component>(
+    object: &Object,
+    handler: &VHandler<Model>,
+    scope: &Scope<Model>,
+) -> (SignalHandlerId, Rc<VHandlerSlot>) {
+    let slot = Rc::new(VHandlerSlot::new((handler.handler)(scope)));
+    let handle = (handler.connect)(object, slot.clone());
+    (handle, slot)
 }
 
-// Gtk has many strange ways of adding children to a parent.
-fn add_child<Model: Component>(
+/// Add a newly built child to its parent and apply its child properties.
+///
+/// Fragments add their own children to the parent as they build them, so we
+/// leave them where they are.
+pub(super) fn attach_child<Model: 'static + Component>(
     parent: &Object,
     index: usize,
     total: usize,
     child_spec: &VNode<Model>,
-    child: &Object,
+    child: &mut State<Model>,
 ) {
-    if let Some(application) = parent.downcast_ref::<Application>() {
-        if let Some(window) = child.downcast_ref::<Window>() {
-            application.add_window(window);
-        } else if let Some(action) = child.downcast_ref::<Action>() {
-            application.add_action(action);
-        } else {
-            panic!(
-                "Application's children must be Windows or Actions, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(button) = parent.downcast_ref::<MenuButton>() {
-        // MenuButton: can only have a single child, either a `Menu` set with
-        // `set_popup` or any other `Widget` set with `set_popover`.
-        if total > 1 {
-            panic!(
-                "MenuButton can only have 1 child, but {} were found.",
-                total,
-            );
+    if !child.is_fragment() {
+        for object in child.objects() {
+            add_child(parent, index, total, child_spec.get_child_props(), &object);
         }
-        if let Some(menu) = child.downcast_ref::<Menu>() {
-            button.set_popup(Some(menu));
-        } else if let Some(widget) = child.downcast_ref::<Widget>() {
-            button.set_popover(Some(widget));
-        } else {
-            panic!(
-                "MenuButton's children must be Widgets, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(item) = parent.downcast_ref::<MenuItem>() {
-        // MenuItem: single child, must be a `Menu`, set with `set_submenu`.
-        if total > 1 {
-            panic!("MenuItem can only have 1 child, but {} were found.", total);
-        }
-        if let Some(menu) = child.downcast_ref::<Menu>() {
-            item.set_submenu(Some(menu));
-        } else {
-            panic!(
-                "MenuItem can only take children of type Menu, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(dialog) = parent.downcast_ref::<Dialog>() {
-        // Dialog: children must be added to the Dialog's content area through
-        // get_content_area().
-        if let Some(widget) = child.downcast_ref::<Widget>() {
-            dialog.get_content_area().add(widget);
-        } else {
-            panic!(
-                "Dialog's children must be Widgets, but {} was found.",
-                child.get_type()
-            );
+    }
+    child.apply_child_props(child_spec, parent);
+}
+
+/// Tell each of a parent's children which object comes just before it, the
+/// first one being told `anchor`.
+pub(super) fn set_anchors<Model: 'static + Component>(
+    children: &[State<Model>],
+    anchor: Option<&Object>,
+) {
+    let mut previous = anchor.cloned();
+    for child in children {
+        child.set_anchor(previous.as_ref());
+        if let Some(last) = child.objects().pop() {
+            previous = Some(last);
         }
-    } else if let Some(window) = parent.downcast_ref::<ApplicationWindow>() {
-        // ApplicationWindow: takes any number of Actions, optionally one
-        // ShortcutsWindow added with `set_help_overlay()`, and either 1 or 2
-        // Widgets. If 1, it's the main widget. If 2, the first is added with
-        // `set_titlebar()` and the second is the main widget.
-        if let Some(action) = child.downcast_ref::<Action>() {
-            window.add_action(action);
-        } else if let Some(help_overlay) = child.downcast_ref::<ShortcutsWindow>() {
-            window.set_help_overlay(Some(help_overlay));
-        } else if let Some(widget) = child.downcast_ref::<Widget>() {
-            match window.get_child() {
-                None => window.add(widget),
-                Some(ref titlebar) if window.get_titlebar().is_none() => {
-                    window.remove(titlebar);
-                    window.set_titlebar(Some(titlebar));
-                    window.add(widget);
+    }
+}
+
+/// Find the position of an object among its parent's children.
+///
+/// Some containers wrap their children in widgets of their own, like the rows
+/// of a `ListBox`, so this is really the position of whatever wraps it.
+pub(super) fn child_position(
+    parent: &Container,
+    siblings: &[Widget],
+    object: &Object,
+) -> Option<usize> {
+    let child = direct_child(parent, object.downcast_ref::<Widget>()?);
+    siblings.iter().position(|sibling| *sibling == child)
+}
+
+/// Patch a parent's list of children against a list of specs, building,
+/// removing and moving children as needed.
+///
+/// `offset` is the position of the first of these children among the parent's
+/// children. If `shared` is set, the parent has other children besides these,
+/// so anything we add ends up out of place and has to be moved.
+pub(super) fn patch_children<Model: 'static + Component>(
+    parent: &Object,
+    children: &mut Vec<State<Model>>,
+    specs: &[&VNode<Model>],
+    offset: usize,
+    shared: bool,
+    scope: &Scope<Model>,
+) {
+    let total = specs.len();
+    let mut old: Vec<Option<State<Model>>> = children.drain(..).map(Some).collect();
+    let keyed: HashMap<String, usize> = old
+        .iter()
+        .enumerate()
+        .filter_map(|(index, child)| {
+            let key = child.as_ref().and_then(State::key)?;
+            Some((key.to_owned(), index))
+        })
+        .collect();
+
+    // Find an existing child for each spec and patch it in place. Keyed
+    // specs look for the child with the same key, wherever it is; unkeyed
+    // specs take the unkeyed child at the same index, if there is one.
+    let mut matched: Vec<Option<(usize, State<Model>)>> = Vec::with_capacity(total);
+    for (index, spec) in specs.iter().enumerate() {
+        let candidate = match spec.get_key() {
+            Some(key) => keyed.get(key).copied(),
+            None => match old.get(index) {
+                Some(Some(child)) if child.key().is_none() => Some(index),
+                _ => None,
+            },
+        };
+        let reused = match candidate {
+            Some(old_index) if matches!(&old[old_index], Some(child) if child.matches(spec)) => {
+                let mut child = old[old_index].take().unwrap();
+                if child.patch(spec, Some(parent), scope) {
+                    Some((old_index, child))
+                } else {
+                    old.push(Some(child));
+                    None
                 }
-                _ => panic!("ApplicationWindow can have at most two Widget children."),
             }
-        } else {
-            panic!(
-                "ApplicationWindow's children must be Actions or Widgets, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(window) = parent.downcast_ref::<Window>() {
-        // Window: takes only 1 or 2 Widgets. If 1 widget child, it's the
-        // window's main widget. If 2, the first is the title bar and the second
-        // is the main widget. More than 2 goes boom.
-        if let Some(widget) = child.downcast_ref::<Widget>() {
-            if total == 2 && index == 0 {
-                window.set_titlebar(Some(widget));
-            } else {
-                window.add(widget);
-            }
-        } else {
-            panic!(
-                "Window's children must be Widgets, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(parent) = parent.downcast_ref::<Bin>() {
-        // Bin: can only have a single child.
-        if total > 1 {
-            panic!("Bins can only have 1 child, but {} were found.", total);
-        }
-        if let Some(widget) = child.downcast_ref::<Widget>() {
-            parent.add(widget);
-        } else {
-            panic!(
-                "Bin's child must be a Widget, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(parent) = parent.downcast_ref::<GtkBox>() {
-        // Box: added normally, except one widget can be added using
-        // set_center_widget() if it has the center_widget=true child property
-        // (which is faked in ext.rs). More than one child with this property is
-        // undefined behaviour.
-        if let Some(widget) = child.downcast_ref::<Widget>() {
-            if child_spec.get_child_prop("center_widget").is_some() {
-                parent.set_center_widget(Some(widget));
-            } else {
-                parent.add(widget);
-            }
-        } else {
-            panic!(
-                "Box's children must be Widgets, but {} was found.",
-                child.get_type()
-            );
+            _ => None,
+        };
+        matched.push(reused);
+    }
+
+    // Remove every child that wasn't reused. This has to happen before we
+    // add anything, as some containers only hold a single child.
+    for child in old.into_iter().flatten() {
+        for object in child.objects() {
+            remove_child(parent, &object);
         }
-    } else if let Some(parent) = parent.downcast_ref::<HeaderBar>() {
-        // HeaderBar: added normally, except one widget can be added using
-        // set_custom_title if it has the custom_title=true child property
-        // (which is faked in ext.rs). More than one child with this property is
-        // undefined behaviour.
-        if let Some(widget) = child.downcast_ref::<Widget>() {
-            if child_spec.get_child_prop("custom_title").is_some() {
-                parent.set_custom_title(Some(widget));
-            } else {
-                parent.add(widget);
+        child.unmount();
+    }
+
+    // Build new children and put everything in its place, moving only the
+    // children which `out_of_place()` says have to be moved. A child may stand
+    // for more than one object if it's a component which renders a fragment,
+    // so `position` counts objects rather than children.
+    let old_indices: Vec<Option<usize>> = matched
+        .iter()
+        .map(|slot| slot.as_ref().map(|(old_index, _)| *old_index))
+        .collect();
+    let moves = out_of_place(&old_indices);
+    let mut position = offset;
+    for (index, ((spec, slot), moved)) in specs.iter().zip(matched).zip(moves).enumerate() {
+        let (child, built) = match slot {
+            Some((_, child)) => (child, false),
+            None => {
+                let mut child = State::build(spec, Some(parent), scope);
+                attach_child(parent, index, total, spec, &mut child);
+                for object in child.objects() {
+                    if let Some(widget) = object.downcast_ref::<Widget>() {
+                        widget.show();
+                    }
+                }
+                (child, true)
             }
-        } else {
-            panic!(
-                "HeaderBar's children must be Widgets, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(parent) = parent.downcast_ref::<Grid>() {
-        if let Some(widget) = child.downcast_ref::<Widget>() {
-            // by default we put widgets in the top left corner of the grid
-            // with row and col span of 1; this would typically get overridden
-            // via props but setting the default is important in order to avoid
-            // making the user specify these for every single child widget
-            parent.attach(widget, 0, 0, 1, 1);
-        } else {
-            panic!(
-                "Grid's children must be Widgets, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(parent) = parent.downcast_ref::<Notebook>() {
-        // Notebook: added normally, except one widget can be added using
-        // set_action_widget if it has the action_widget_start or
-        // action_widget_end child property (which are faked in ext.rs). More
-        // than one child with each of these properties is undefined behaviour.
-        if let Some(widget) = child.downcast_ref::<Widget>() {
-            if child_spec.get_child_prop("action_widget_start").is_some() {
-                parent.set_action_widget(widget, gtk::PackType::Start);
-            } else if child_spec.get_child_prop("action_widget_end").is_some() {
-                parent.set_action_widget(widget, gtk::PackType::End);
-            } else {
-                parent.add(widget);
+        };
+        let objects = child.objects();
+        if moved || (built && shared) {
+            for (n, object) in objects.iter().enumerate() {
+                reorder_child(parent, position + n, spec.get_child_props(), object);
             }
-        } else {
-            panic!(
-                "Notebook's children must be Widgets, but {} was found.",
-                child.get_type()
-            );
-        }
-    } else if let Some(container) = parent.downcast_ref::<Container>() {
-        if let Some(widget) = child.downcast_ref::<Widget>() {
-            container.add(widget);
-        } else {
-            panic!(
-                "Container's children must be Widgets, but {} was found.",
-                child.get_type()
-            );
         }
-    } else {
-        panic!("Don't know how to add children to a {}", parent.get_type());
+        position += objects.len();
+        children.push(child);
     }
-    // Apply child properties
-    for prop in child_spec.get_child_props() {
-        (prop.set)(child.upcast_ref(), Some(parent), true);
+}
+
+/// Work out which children have to be moved to put them in their new order,
+/// given the old index of each child in the new order, or `None` for a child
+/// that's just been built.
+///
+/// Reused children start out in their old order and new children are added at
+/// the end, and children are put in place from first to last, so a child can
+/// stay where it is only if everything after it in the new order is also after
+/// it now. That makes a reused child out of place if a child with a lower old
+/// index comes after it, and a new child out of place if any reused child comes
+/// after it.
+fn out_of_place(old_indices: &[Option<usize>]) -> Vec<bool> {
+    let mut moves = vec![false; old_indices.len()];
+    let mut lowest_after: Option<usize> = None;
+    for (index, old_index) in old_indices.iter().enumerate().rev() {
+        moves[index] = match (old_index, lowest_after) {
+            (_, None) => false,
+            (Some(old_index), Some(lowest)) => *old_index > lowest,
+            (None, Some(_)) => true,
+        };
+        if let Some(old_index) = *old_index {
+            lowest_after = Some(lowest_after.map_or(old_index, |lowest| lowest.min(old_index)));
+        }
     }
+    moves
+}
+
+/// Find where a state's objects are among their parent's children, as the
+/// position of the first one and the number of children the parent has.
+///
+/// If the state has no objects, this is where they'd go, just after the
+/// placement's anchor. Parents which aren't containers don't have positions.
+pub(super) fn find_place(
+    parent: &Object,
+    objects: &[Object],
+    placement: &Placement,
+) -> Option<(usize, usize)> {
+    let container = parent.downcast_ref::<Container>()?;
+    let siblings = container.get_children();
+    let position = match (objects.first(), &*placement.anchor.borrow()) {
+        (Some(first), _) => child_position(container, &siblings, first)?,
+        (None, Some(anchor)) => child_position(container, &siblings, anchor)? + 1,
+        (None, None) => 0,
+    };
+    Some((position, siblings.len()))
 }
 
-fn remove_child(parent: &Object, child: &Object) {
-    // There are also special cases for removing children.
-    if let Some(application) = parent.downcast_ref::<Application>() {
-        if let Some(window) = child.downcast_ref::<Window>() {
-            application.remove_window(window);
-        } else if let Some(action) = child.downcast_ref::<Action>() {
-            application.remove_action(&action.get_name().expect("Action unexpectedly has no name"));
-        } else {
-            panic!(
-                "Applications can only contain Windows, but was asked to remove a {}.",
-                child.get_type()
-            );
+/// Put a newly built state in the place of an old one among the parent's
+/// children, giving it the child properties the old one had.
+///
+/// This is how the root of a component's view is rebuilt when it changes type,
+/// as the parent's own state doesn't know it's changed. The old state's
+/// objects and place have to be found before the new one is built, as a
+/// subcomponent at the root shares the placement they're kept in and records
+/// its own objects there as soon as it's built.
+pub(super) fn replace_child<Model: 'static + Component>(
+    parent: &Object,
+    old: State<Model>,
+    old_objects: &[Object],
+    place: Option<(usize, usize)>,
+    new: &mut State<Model>,
+    placement: &Placement,
+) {
+    let child_props = placement.child_props.borrow();
+    for object in old_objects {
+        remove_child(parent, object);
+    }
+    old.unmount_root(old_objects);
+
+    // `add_child()` wants to know where the child goes and how many siblings
+    // it has.
+    let objects = new.objects();
+    if !new.is_fragment() {
+        let (index, total) = match place {
+            Some((position, siblings)) => (
+                position,
+                siblings.saturating_sub(old_objects.len()) + objects.len(),
+            ),
+            None => (0, objects.len()),
+        };
+        for (n, object) in objects.iter().enumerate() {
+            add_child(parent, index + n, total, child_props.as_slice(), object);
         }
-    } else if let Some(container) = parent.downcast_ref::<Container>() {
-        // For a Container and a Widget child, we should always be able to call
-        // `Container::remove`.
-        if let Some(child_widget) = child.downcast_ref::<Widget>() {
-            container.remove(child_widget);
-        } else {
-            panic!(
-                "Containers can only contain Widgets but was asked to remove a {}.",
-                child.get_type()
-            );
+    }
+    if let Some((position, _)) = place {
+        for (n, object) in objects.iter().enumerate() {
+            reorder_child(parent, position + n, child_props.as_slice(), object);
         }
-    } else {
-        panic!(
-            "Don't know how to remove a child from a {}",
-            parent.get_type()
-        );
     }
+    child_props.reapply(&objects, Some(parent));
 }
 
 impl<Model: 'static + Component> GtkState<Model> {
@@ -280,22 +342,32 @@ impl<Model: 'static + Component> GtkState<Model> {
         scope: &Scope<Model>,
     ) -> Self {
         // Build this object
-        let object: Object = build_obj(&vobj);
+        let (object, constructed): (Object, _) = build_obj(vobj);
 
-        // Apply properties
-        for prop in &vobj.properties {
-            (prop.set)(object.upcast_ref(), parent, true);
-        }
+        // Apply properties, except the ones it was constructed with
+        let mut properties = PropertyValues::default();
+        properties.apply_built(&vobj.properties, &constructed, &object, parent);
 
         // Apply handlers
         let mut handlers = HashMap::new();
         for handler in &vobj.handlers {
-            let handle = (handler.set)(object.upcast_ref(), scope);
-            handlers.insert((handler.name, handler.id), handle);
+            handlers.insert((handler.name, handler.id), connect(&object, handler, scope));
         }
 
+        // Fill in the reference to it, if any
+        let mut reference = None;
+        VRef::update(
+            &mut reference,
+            vobj.reference.as_ref(),
+            RefTarget::Object(object.clone()),
+        );
+
         GtkState {
             object: object.upcast(),
+            key: vobj.key.clone(),
+            child_props: PropertyValues::default(),
+            reference,
+            properties,
             handlers,
             children: Vec::new(),
         }
@@ -304,13 +376,14 @@ impl<Model: 'static + Component> GtkState<Model> {
     pub(crate) fn build_children(&mut self, vobj: &VObject<Model>, scope: &Scope<Model>) {
         let object = &self.object;
         // Build children
-        let total_children = vobj.children.len();
-        for (index, child_spec) in vobj.children.iter().enumerate() {
-            let child = State::build(child_spec, Some(&object), &scope);
-            let child_object = child.object().clone();
-            add_child(&object, index, total_children, child_spec, &child_object);
+        let child_specs = VNode::flatten(&vobj.children);
+        let total_children = child_specs.len();
+        for (index, child_spec) in child_specs.into_iter().enumerate() {
+            let mut child = State::build(child_spec, Some(&object), &scope);
+            attach_child(&object, index, total_children, child_spec, &mut child);
             self.children.push(child);
         }
+        set_anchors(&self.children, None);
 
         // Show this object, if it's a widget
         if let Some(widget) = self.object.downcast_ref::<Widget>() {
@@ -334,143 +407,68 @@ impl<Model: 'static + Component> GtkState<Model> {
         parent: Option<&Object>,
         scope: &Scope<Model>,
     ) -> bool {
-        // Patch children
-        let mut to_remove = None;
-        let mut to_append = Vec::new();
-        let mut reconstruct_from = None;
-        for index in 0..(self.children.len().max(vobj.children.len())) {
-            match (self.children.get_mut(index), vobj.children.get(index)) {
-                (Some(State::Component(target)), Some(spec_item)) => {
-                    match spec_item {
-                        VNode::Object(_) => {
-                            // Component has become a widget; reconstruct from here
-                            reconstruct_from = Some(index);
-                            break;
-                        }
-                        VNode::Component(ref spec) => {
-                            if !target.patch(spec, Some(&self.object), scope) {
-                                reconstruct_from = Some(index);
-                                break;
-                            }
-                        }
-                    }
-                }
-                (Some(State::Gtk(target)), Some(spec_item)) => {
-                    match spec_item {
-                        VNode::Object(ref spec) => {
-                            if target.object.get_type() == spec.object_type {
-                                // Objects have same type; patch down
-                                target.patch(spec, Some(&self.object), scope);
-                            } else {
-                                // Objects are different, need to reconstruct everything from here
-                                reconstruct_from = Some(index);
-                                break;
-                            }
-                        }
-                        VNode::Component(_) => {
-                            // Gtk object has turned into a component; reconstruct from here
-                            reconstruct_from = Some(index);
-                            break;
-                        }
-                    }
-                }
-                (Some(_), None) => {
-                    // Extraneous Gtk object; delete
-                    if to_remove.is_none() {
-                        to_remove = Some(index);
-                    }
-                    break;
-                }
-                (None, Some(spec)) => {
-                    // New spec; construct
-                    let state = State::build(spec, Some(&self.object), scope);
-                    add_child(
-                        &self.object,
-                        index,
-                        vobj.children.len(),
-                        spec,
-                        state.object(),
-                    );
-                    to_append.push(state);
-                }
-                (None, None) => break,
-            }
-        }
-        if let Some(index) = reconstruct_from {
-            // Remove all previous children from here onwards
-            if self.object.is::<Window>() && index == 0 && self.children.len() == 2 {
-                panic!("Can't remove a title bar widget from an existing Window!");
-            }
-            for child in self.children.drain(index..) {
-                remove_child(&self.object, child.object());
-                child.unmount();
-            }
-            // Rebuild children from new specs
-            for (index, child_spec) in vobj.children.iter().enumerate().skip(index) {
-                let state = State::build(child_spec, Some(&self.object), scope);
-                add_child(
-                    &self.object,
-                    index,
-                    vobj.children.len(),
-                    child_spec,
-                    state.object(),
-                );
-                if let Some(w) = state.widget() {
-                    w.show()
-                }
-                self.children.push(state);
-            }
-        } else {
-            // Remove children flagged as extraneous
-            if let Some(remove_from) = to_remove {
-                if self.object.is::<Window>() && remove_from == 1 && self.children.len() == 2 {
-                    panic!("Can't remove a title bar widget from an existing Window!");
-                }
-                for child in self.children.drain(remove_from..) {
-                    remove_child(&self.object, &child.object());
-                    child.unmount();
-                }
-            }
-            // Or append newly constructed children
-            if self.object.is::<Window>() && !to_append.is_empty() && self.children.len() == 1 {
-                panic!("Can't add a title bar widget to an existing Window!");
-            }
-            for child in to_append {
-                if let Some(w) = child.widget() {
-                    w.show()
-                }
-                self.children.push(child);
-            }
+        // Construct properties which have changed need a new object
+        if vobj
+            .properties
+            .iter()
+            .any(|prop| prop.needs_rebuild(self.properties.get(prop.name)))
+        {
+            return false;
         }
 
+        // Patch children
+        patch_children(
+            &self.object,
+            &mut self.children,
+            &VNode::flatten(&vobj.children),
+            0,
+            false,
+            scope,
+        );
+        set_anchors(&self.children, None);
+
         // Patch properties
-        self.patch_properties(&vobj.properties, parent);
+        self.properties
+            .apply(&vobj.properties, from_ref(&self.object), parent);
 
         // Patch child properties
-        self.patch_properties(&vobj.child_props, parent);
+        self.child_props
+            .apply(&vobj.child_props, from_ref(&self.object), parent);
 
         // Patch handlers
         self.patch_handlers(&vobj.handlers, scope);
 
-        true
-    }
+        // Patch reference
+        VRef::update(
+            &mut self.reference,
+            vobj.reference.as_ref(),
+            RefTarget::Object(self.object.clone()),
+        );
 
-    fn patch_properties(&mut self, properties: &[VProperty], parent: Option<&Object>) {
-        for prop in properties {
-            (prop.set)(self.object.upcast_ref(), parent, false);
-        }
+        true
     }
 
     fn patch_handlers(&mut self, handlers: &[VHandler<Model>], scope: &Scope<Model>) {
-        // FIXME need to store and match IDs
         let mut seen = HashSet::new();
         let mut remove = Vec::new();
         for handler in handlers {
             let key = (handler.name, handler.id);
             seen.insert(key.to_owned());
-            if let std::collections::hash_map::Entry::Vacant(entry) = self.handlers.entry(key) {
-                let handle = (handler.set)(self.object.upcast_ref(), scope);
-                entry.insert(handle);
+            match self.handlers.entry(key) {
+                Entry::Occupied(mut entry) => {
+                    // Swap the new closure into the connected slot, unless for some
+                    // reason its type has changed, in which case we have to reconnect.
+                    let closure = (handler.handler)(scope);
+                    if entry.get().1.accepts(&closure) {
+                        entry.get().1.replace(closure);
+                    } else {
+                        let (handle, _) = entry.insert(connect(&self.object, handler, scope));
+                        self.object.disconnect(handle);
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(connect(&self.object, handler, scope));
+                }
             }
         }
         for key in self.handlers.keys() {
@@ -480,7 +478,7 @@ impl<Model: 'static + Component> GtkState<Model> {
         }
         for key in remove {
             let obj: &Object = self.object.upcast_ref();
-            obj.disconnect(self.handlers.remove(&key).unwrap());
+            obj.disconnect(self.handlers.remove(&key).unwrap().0);
         }
     }
 
@@ -488,6 +486,9 @@ impl<Model: 'static + Component> GtkState<Model> {
         for child in self.children {
             child.unmount();
         }
+        if let Some(reference) = &self.reference {
+            reference.detach(&RefTarget::Object(self.object.clone()));
+        }
         if let Ok(widget) = self.object.downcast::<Widget>() {
             #[allow(unsafe_code)]
             unsafe {
@@ -496,3 +497,98 @@ impl<Model: 'static + Component> GtkState<Model> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::out_of_place;
+
+    /// Put children in order the way `patch_children()` does, starting from
+    /// the reused children in their old order followed by the new ones, and
+    /// moving only the ones `out_of_place()` picks. Returns the resulting
+    /// order and the number of moves made.
+    fn reorder(old_indices: &[Option<usize>]) -> (Vec<Option<usize>>, usize) {
+        let mut current: Vec<Option<usize>> =
+            old_indices.iter().copied().flatten().map(Some).collect();
+        current.sort_unstable();
+        let mut moved = 0;
+        for (position, (old_index, out_of_place)) in old_indices
+            .iter()
+            .zip(out_of_place(old_indices))
+            .enumerate()
+        {
+            if old_index.is_none() {
+                current.push(None);
+            }
+            if out_of_place {
+                let from = match old_index {
+                    Some(_) => current.iter().position(|child| child == old_index).unwrap(),
+                    None => current.len() - 1,
+                };
+                let child = current.remove(from);
+                current.insert(position, child);
+                moved += 1;
+            }
+        }
+        (current, moved)
+    }
+
+    fn check(old_indices: &[Option<usize>], expected_moves: usize) {
+        let (order, moved) = reorder(old_indices);
+        assert_eq!(order, old_indices);
+        assert_eq!(moved, expected_moves);
+    }
+
+    #[test]
+    fn unchanged_children_stay_in_place() {
+        check(&[Some(0), Some(1), Some(2), Some(3)], 0);
+        check(&[Some(0), Some(2), Some(5)], 0);
+        assert_eq!(out_of_place(&[]), Vec::<bool>::new());
+    }
+
+    #[test]
+    fn appended_children_stay_in_place() {
+        check(&[Some(0), Some(1), None, None], 0);
+        check(&[None, None], 0);
+    }
+
+    #[test]
+    fn inserted_children_are_moved() {
+        check(&[None, Some(0), Some(1)], 1);
+        check(&[Some(0), None, Some(1), None], 1);
+    }
+
+    #[test]
+    fn moved_children_are_moved() {
+        assert_eq!(
+            out_of_place(&[Some(3), Some(0), Some(1), Some(2)]),
+            vec![true, false, false, false]
+        );
+        check(&[Some(3), Some(0), Some(1), Some(2)], 1);
+        check(&[Some(2), Some(3), Some(0), Some(1)], 2);
+        check(&[Some(3), Some(2), Some(1), Some(0)], 3);
+        check(&[Some(1), None, Some(0), Some(4), None, Some(2)], 4);
+    }
+
+    #[test]
+    fn every_permutation_ends_up_in_order() {
+        fn permutations(items: Vec<Option<usize>>) -> Vec<Vec<Option<usize>>> {
+            if items.len() <= 1 {
+                return vec![items];
+            }
+            let mut result = Vec::new();
+            for index in 0..items.len() {
+                let mut rest = items.clone();
+                let item = rest.remove(index);
+                for mut permutation in permutations(rest) {
+                    permutation.insert(0, item);
+                    result.push(permutation);
+                }
+            }
+            result
+        }
+        for permutation in permutations(vec![Some(0), Some(1), Some(2), None, Some(4), None]) {
+            let (order, _) = reorder(&permutation);
+            assert_eq!(order, permutation);
+        }
+    }
+}
@@ -1,19 +1,117 @@
-use glib::{Cast, Object};
-use gtk::{self, Widget};
+use glib::{Object, ObjectExt};
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
 use crate::component::Component;
 use crate::scope::Scope;
-use crate::vnode::VNode;
+use crate::vnode::{VNode, VProperty};
+
+mod children_state;
+pub(crate) use children_state::{AnyChildrenState, ChildrenState};
 
 mod component_state;
 pub(crate) use component_state::ComponentState;
 
+mod fragment_state;
+use fragment_state::FragmentState;
+
 mod gtk_state;
 use gtk_state::GtkState;
 
+/// The properties last applied to an object, which the next render's
+/// properties are diffed against.
+#[derive(Default)]
+pub(crate) struct PropertyValues(Vec<VProperty>);
+
+impl PropertyValues {
+    /// Apply properties to a list of objects, skipping those whose values
+    /// haven't changed since they were last applied.
+    pub(crate) fn apply(
+        &mut self,
+        properties: &[VProperty],
+        objects: &[Object],
+        parent: Option<&Object>,
+    ) {
+        for prop in properties {
+            let previous = self.get(prop.name).map(|previous| &*previous.value);
+            for object in objects {
+                (prop.set)(object, parent, previous);
+            }
+        }
+        for previous in &self.0 {
+            if let Some(unset) = &previous.unset {
+                if !properties.iter().any(|prop| prop.name == previous.name) {
+                    for object in objects {
+                        unset(object, parent, &*previous.value);
+                    }
+                }
+            }
+        }
+        self.0 = properties.to_vec();
+    }
+
+    /// Apply properties to a newly built object, except for the ones it was
+    /// given when it was constructed.
+    pub(crate) fn apply_built(
+        &mut self,
+        properties: &[VProperty],
+        constructed: &[&str],
+        object: &Object,
+        parent: Option<&Object>,
+    ) {
+        for prop in properties {
+            if !constructed.contains(&prop.name) {
+                (prop.set)(object, parent, None);
+            }
+        }
+        self.0 = properties.to_vec();
+    }
+
+    /// Apply the properties last applied again, in full, to a new list of
+    /// objects.
+    pub(crate) fn reapply(&self, objects: &[Object], parent: Option<&Object>) {
+        for prop in &self.0 {
+            for object in objects {
+                (prop.set)(object, parent, None);
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&VProperty> {
+        self.0.iter().find(|prop| prop.name == name)
+    }
+
+    pub(crate) fn as_slice(&self) -> &[VProperty] {
+        &self.0
+    }
+}
+
+/// Where a component's view has been put in its parent.
+///
+/// This is shared between the state the parent keeps for a component and the
+/// component's own task, which keeps the list of objects up to date as it
+/// re-renders, and which needs the parent's child properties to put a new root
+/// object in the old one's place. A component at the root of another's view
+/// shares its placement with that component.
+#[derive(Default)]
+pub struct Placement {
+    pub(crate) objects: RefCell<Vec<Object>>,
+    /// Whether the view is a fragment, which adds its objects to the parent
+    /// itself. This can change when the view's root is replaced.
+    pub(crate) fragment: Cell<bool>,
+    pub(crate) child_props: RefCell<PropertyValues>,
+    /// The parent's object just before ours, or `None` if ours come first,
+    /// which is how a fragment which has run out of objects still knows where
+    /// to put new ones.
+    pub(crate) anchor: RefCell<Option<Object>>,
+}
+
 pub(crate) enum State<Model: Component> {
     Gtk(GtkState<Model>),
     Component(ComponentState<Model>),
+    Fragment(FragmentState<Model>),
+    Children(Box<dyn AnyChildrenState>),
 }
 
 impl<Model: 'static + Component> State<Model> {
@@ -26,35 +124,66 @@ impl<Model: 'static + Component> State<Model> {
         match vnode {
             VNode::Object(object) => State::Gtk(GtkState::build(object, parent, scope)),
             VNode::Component(vcomp) => {
-                let comp = (vcomp.constructor)(&vcomp.props, parent, &vcomp.child_props, scope);
+                let placement = Rc::new(Placement::default());
+                vcomp.bind_children(scope);
+                let mut comp = (vcomp.constructor)(&vcomp.props, parent, placement, scope);
+                comp.key = vcomp.key.clone();
+                comp.set_reference(vcomp.reference.as_ref());
+                comp.build_children();
                 State::Component(comp)
             }
+            VNode::Fragment(children) => State::Fragment(FragmentState::build(
+                children,
+                parent,
+                Rc::new(Placement::default()),
+                scope,
+            )),
+            VNode::Children(children) => State::Children(children.build(parent)),
         }
     }
 
-    /// Build a full state from a `VItem` spec.
+    /// Build the root object of a `VItem` spec, but not its children.
+    ///
+    /// You must call `build_children()` to finalise construction.
+    ///
+    /// This is only for the root of a component's view, whose placement in
+    /// its parent a subcomponent at the root shares.
     pub(crate) fn build_root(
         vnode: &VNode<Model>,
         parent: Option<&Object>,
+        placement: &Rc<Placement>,
         scope: &Scope<Model>,
     ) -> Self {
         match vnode {
             VNode::Object(object) => State::Gtk(GtkState::build_root(object, parent, scope)),
-            VNode::Component(_vcomp) => {
-                // let comp = (vcomp.constructor)(&vcomp.props, parent, &vcomp.child_props, scope);
-                // State::Component(comp)
-                unimplemented!()
+            VNode::Component(vcomp) => {
+                vcomp.bind_children(scope);
+                let mut comp = (vcomp.constructor)(&vcomp.props, parent, placement.clone(), scope);
+                comp.key = vcomp.key.clone();
+                comp.set_reference(vcomp.reference.as_ref());
+                State::Component(comp)
             }
+            VNode::Fragment(children) => State::Fragment(FragmentState::build(
+                children,
+                parent,
+                placement.clone(),
+                scope,
+            )),
+            VNode::Children(children) => State::Children(children.build(parent)),
         }
     }
 
     pub(crate) fn build_children(&mut self, vnode: &VNode<Model>, scope: &Scope<Model>) {
-        match vnode {
-            VNode::Object(vobject) => match self {
-                State::Gtk(gtk_state) => gtk_state.build_children(vobject, scope),
-                _ => unimplemented!(),
-            },
-            _ => unimplemented!(),
+        match (self, vnode) {
+            (State::Gtk(gtk_state), VNode::Object(vobject)) => {
+                gtk_state.build_children(vobject, scope)
+            }
+            // A component builds its children from its own view.
+            (State::Component(comp_state), VNode::Component(_)) => comp_state.build_children(),
+            // Fragments and children are built in full by `build_root()`.
+            (State::Fragment(_), VNode::Fragment(_)) | (State::Children(_), VNode::Children(_)) => {
+            }
+            _ => panic!("tried to build children from a spec which doesn't match the state"),
         }
     }
 
@@ -71,12 +200,78 @@ impl<Model: 'static + Component> State<Model> {
         match vnode {
             VNode::Object(object) => match self {
                 State::Gtk(state) => state.patch(object, parent, scope),
-                State::Component(_) => false,
+                _ => false,
             },
             VNode::Component(vcomp) => match self {
                 State::Component(state) => state.patch(vcomp, parent, scope),
-                State::Gtk(_) => false,
+                _ => false,
+            },
+            VNode::Fragment(children) => match self {
+                State::Fragment(state) => state.patch(children, scope),
+                _ => false,
             },
+            VNode::Children(children) => match self {
+                State::Children(state) => state.patch(children.nodes()),
+                _ => false,
+            },
+        }
+    }
+
+    /// Test whether this state can be patched in place with a `VItem` spec.
+    ///
+    /// This is the case when both are Gtk objects of the same type, both are
+    /// components of the same type, or both are fragments or children. Children
+    /// passed in by different types of component still can't be patched.
+    pub(crate) fn matches(&self, vnode: &VNode<Model>) -> bool {
+        match (self, vnode) {
+            (State::Gtk(state), VNode::Object(object)) => {
+                state.object.get_type() == object.object_type
+            }
+            (State::Component(state), VNode::Component(vcomp)) => {
+                state.model_type == vcomp.model_type
+            }
+            (State::Fragment(_), VNode::Fragment(_)) => true,
+            (State::Children(_), VNode::Children(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Apply the child properties from a `VItem` spec to this state's objects.
+    pub(crate) fn apply_child_props(&mut self, vnode: &VNode<Model>, parent: &Object) {
+        let objects = self.objects();
+        let props = vnode.get_child_props();
+        match self {
+            State::Gtk(state) => state.child_props.apply(props, &objects, Some(parent)),
+            State::Component(state) => state.apply_child_props(props, &objects, parent),
+            // Fragments don't have any properties of their own.
+            State::Fragment(_) | State::Children(_) => {}
+        }
+    }
+
+    /// Replace this state with a new one built from a `VItem` spec which it
+    /// couldn't be patched with.
+    ///
+    /// This is for the root of a component's view, which isn't its parent's to
+    /// rebuild, so we put the new objects in the old ones' place ourselves and
+    /// give them the child properties the parent gave the old ones.
+    pub(crate) fn replace(
+        &mut self,
+        vnode: &VNode<Model>,
+        parent: Option<&Object>,
+        placement: &Rc<Placement>,
+        scope: &Scope<Model>,
+    ) {
+        let old_objects = self.objects();
+        let place =
+            parent.and_then(|parent| gtk_state::find_place(parent, &old_objects, placement));
+        let mut state = State::build_root(vnode, parent, placement, scope);
+        state.build_children(vnode, scope);
+        let old = std::mem::replace(self, state);
+        match parent {
+            Some(parent) => {
+                gtk_state::replace_child(parent, old, &old_objects, place, self, placement)
+            }
+            None => old.unmount_root(&old_objects),
         }
     }
 
@@ -84,22 +279,188 @@ impl<Model: 'static + Component> State<Model> {
         match self {
             State::Gtk(state) => state.unmount(),
             State::Component(state) => state.unmount(),
+            State::Fragment(state) => state.unmount(),
+            State::Children(state) => state.unmount(),
+        }
+    }
+
+    /// Unmount the root of a component's view, which was rendered as
+    /// `objects`.
+    ///
+    /// A subcomponent at the root shares its placement with the component, and
+    /// with whatever replaces it, so the objects in the placement may not be
+    /// its own any more.
+    pub(crate) fn unmount_root(self, objects: &[Object]) {
+        match self {
+            State::Component(state) => state.unmount_objects(objects),
+            state => state.unmount(),
         }
     }
 
     /// Get the Glib `Object` represented by this state.
-    pub(crate) fn object(&self) -> &Object {
+    ///
+    /// For a fragment, or a component which renders one, this is the first
+    /// of its objects, if it has any.
+    pub(crate) fn object(&self) -> Option<Object> {
         match self {
-            State::Gtk(state) => &state.object,
-            State::Component(state) => &state.object,
+            State::Gtk(state) => Some(state.object.clone()),
+            _ => self.objects().into_iter().next(),
         }
     }
 
-    /// Get the Gtk `Widget` represented by this state, if it has a `Widget`.
-    pub(crate) fn widget(&self) -> Option<&Widget> {
+    /// Get the Glib `Object`s represented by this state, in order.
+    ///
+    /// This is always exactly one object, except for fragments, children and
+    /// components which render them, which can have any number.
+    pub(crate) fn objects(&self) -> Vec<Object> {
+        match self {
+            State::Gtk(state) => vec![state.object.clone()],
+            State::Component(state) => state.placement.objects.borrow().clone(),
+            State::Fragment(state) => state.objects(),
+            State::Children(state) => state.objects(),
+        }
+    }
+
+    /// Test whether this state is a fragment, or something which renders as one.
+    ///
+    /// Fragments add their objects to their parent themselves.
+    pub(crate) fn is_fragment(&self) -> bool {
         match self {
-            State::Gtk(state) => state.object.downcast_ref::<Widget>(),
-            State::Component(state) => state.object.downcast_ref::<Widget>(),
+            State::Gtk(_) => false,
+            State::Component(state) => state.placement.fragment.get(),
+            State::Fragment(_) | State::Children(_) => true,
         }
     }
+
+    /// Tell this state which of its parent's objects comes just before its own,
+    /// or `None` if its own come first.
+    ///
+    /// Only fragments, and components which might render one, need to know.
+    pub(crate) fn set_anchor(&self, anchor: Option<&Object>) {
+        match self {
+            State::Gtk(_) => {}
+            State::Component(state) => *state.placement.anchor.borrow_mut() = anchor.cloned(),
+            State::Fragment(state) => state.set_anchor(anchor),
+            State::Children(state) => state.set_anchor(anchor),
+        }
+    }
+
+    /// Get the key this state was built with, if any.
+    pub(crate) fn key(&self) -> Option<&str> {
+        match self {
+            State::Gtk(state) => state.key.as_deref(),
+            State::Component(state) => state.key.as_deref(),
+            State::Fragment(_) | State::Children(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Placement, State};
+    use crate::component::Component;
+    use crate::scope::Scope;
+    use crate::vnode::{VComponent, VNode, VObject};
+    use crate::Attributes;
+    use futures::channel::mpsc::unbounded;
+    use glib::{Cast, Object, StaticType};
+    use gtk::{prelude::*, Label, Orientation, Widget};
+    use std::rc::Rc;
+
+    /// A label with a widget name to tell it apart by.
+    fn label<Model: Component>(name: &'static str) -> VNode<Model> {
+        let mut properties = Vec::new();
+        Attributes::new()
+            .set("name", name.to_string())
+            .spread(&mut properties);
+        VNode::Object(VObject {
+            object_type: Label::static_type(),
+            key: None,
+            reference: None,
+            constructor: None,
+            properties,
+            child_props: Vec::new(),
+            handlers: Vec::new(),
+            children: Vec::new(),
+        })
+    }
+
+    fn component<Child: 'static + Component<Properties = ()>>() -> VNode<()> {
+        let mut vcomp = VComponent::new::<Child>();
+        vcomp.set_props::<Child>(());
+        VNode::Component(vcomp)
+    }
+
+    fn names(objects: &[Object]) -> Vec<String> {
+        objects
+            .iter()
+            .map(|object| {
+                object
+                    .downcast_ref::<Widget>()
+                    .unwrap()
+                    .get_widget_name()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[derive(Default)]
+    struct First;
+
+    impl Component for First {
+        type Message = ();
+        type Properties = ();
+
+        fn view(&self) -> VNode<Self> {
+            label("first")
+        }
+    }
+
+    #[derive(Default)]
+    struct Second;
+
+    impl Component for Second {
+        type Message = ();
+        type Properties = ();
+
+        fn view(&self) -> VNode<Self> {
+            label("second")
+        }
+    }
+
+    #[test]
+    fn replaces_one_component_root_with_another() {
+        gtk::init().expect("GTK failed to initialise");
+        let container = gtk::Box::new(Orientation::Vertical, 0);
+        let parent: Object = container.clone().upcast();
+        let children = || {
+            let objects: Vec<Object> = container
+                .get_children()
+                .into_iter()
+                .map(Cast::upcast)
+                .collect();
+            names(&objects)
+        };
+        let (sender, _receiver) = unbounded();
+        let scope = Scope::new("test", sender);
+        let placement = Rc::new(Placement::default());
+
+        let before = Label::new(None);
+        before.set_widget_name("before");
+        container.add(&before);
+        let first = component::<First>();
+        let mut state = State::build_root(&first, Some(&parent), &placement, &scope);
+        state.build_children(&first, &scope);
+        container.add(state.object().unwrap().downcast_ref::<Widget>().unwrap());
+        let after = Label::new(None);
+        after.set_widget_name("after");
+        container.add(&after);
+        assert_eq!(children(), ["before", "first", "after"]);
+
+        let second = component::<Second>();
+        state.replace(&second, Some(&parent), &placement, &scope);
+        assert_eq!(children(), ["before", "second", "after"]);
+        assert_eq!(names(&placement.objects.borrow()), ["second"]);
+        assert_eq!(names(&state.objects()), ["second"]);
+    }
 }
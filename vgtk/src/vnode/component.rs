@@ -6,10 +6,11 @@ use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::callback::Callback;
+use crate::children::{ChildNodes, Children};
 use crate::component::Component;
 use crate::scope::Scope;
-use crate::vdom::ComponentState;
-use crate::vnode::VProperty;
+use crate::vdom::{ComponentState, Placement};
+use crate::vnode::{VNode, VProperty, VRef};
 
 pub struct AnyProps {
     valid: AtomicBool,
@@ -53,25 +54,46 @@ impl AnyProps {
 }
 
 type Constructor<Model> =
-    dyn Fn(&AnyProps, Option<&Object>, &[VProperty], &Scope<Model>) -> ComponentState<Model>;
+    dyn Fn(&AnyProps, Option<&Object>, Rc<Placement>, &Scope<Model>) -> ComponentState<Model>;
 
 pub struct VComponent<Model: Component> {
     parent: PhantomData<Model>,
     pub model_type: TypeId,
+    pub key: Option<String>,
+    pub reference: Option<VRef>,
     pub props: AnyProps,
     pub constructor: Box<Constructor<Model>>,
     pub child_props: Vec<VProperty>,
+    children: Vec<Rc<ChildNodes<Model>>>,
 }
 
 impl<Model: 'static + Component> VComponent<Model> {
     pub fn new<Child: 'static + Component>() -> Self {
-        let constructor: Box<Constructor<Model>> = Box::new(ComponentState::build::<Child>);
+        let constructor: Box<Constructor<Model>> = Box::new(ComponentState::build_root::<Child>);
         VComponent {
             parent: PhantomData,
             model_type: TypeId::of::<Child>(),
+            key: None,
+            reference: None,
             props: AnyProps::null(),
             constructor,
             child_props: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Wrap a list of nodes up as `Children` to pass to the component.
+    pub fn children(&mut self, nodes: Vec<VNode<Model>>) -> Children {
+        let nodes = Rc::new(ChildNodes::new(nodes));
+        self.children.push(nodes.clone());
+        Children(Some(nodes))
+    }
+
+    /// Give any `Children` passed to the component the scope of the component
+    /// they came from, which wasn't available when they were created.
+    pub(crate) fn bind_children(&self, scope: &Scope<Model>) {
+        for nodes in &self.children {
+            nodes.bind(scope);
         }
     }
 
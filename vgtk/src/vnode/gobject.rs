@@ -2,11 +2,13 @@ use std::borrow::Borrow;
 
 use glib::{Object, Type};
 
-use super::{VHandler, VNode, VProperty};
+use super::{VHandler, VNode, VProperty, VRef};
 use crate::Component;
 
 pub struct VObject<Model: Component> {
     pub object_type: Type,
+    pub key: Option<String>,
+    pub reference: Option<VRef>,
     pub constructor: Option<Box<dyn Fn() -> Object>>,
     pub properties: Vec<VProperty>,
     pub child_props: Vec<VProperty>,
@@ -1,9 +1,156 @@
 use glib::{signal::SignalHandlerId, Object};
 
+use std::any::Any;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
 use crate::{scope::Scope, Component};
 
 pub struct VHandler<Model: Component> {
     pub name: &'static str,
     pub id: &'static str,
-    pub set: Box<dyn Fn(&Object, &Scope<Model>) -> SignalHandlerId>,
+    pub handler: Box<dyn Fn(&Scope<Model>) -> Rc<dyn Any>>,
+    pub connect: Box<dyn Fn(&Object, Rc<VHandlerSlot>) -> SignalHandlerId>,
+}
+
+impl<Model: Component> VHandler<Model> {
+    /// Construct a signal handler.
+    ///
+    /// `handler` builds the closure to run when the signal is emitted, and
+    /// `connect` connects a closure to the signal which looks up and calls
+    /// whatever handler closure is currently in the slot it's given.
+    pub fn new<F, H, C>(name: &'static str, id: &'static str, handler: H, connect: C) -> Self
+    where
+        F: 'static,
+        H: Fn(&Scope<Model>) -> F + 'static,
+        C: Fn(&Object, Rc<VHandlerSlot>, PhantomData<F>) -> SignalHandlerId + 'static,
+    {
+        VHandler {
+            name,
+            id,
+            handler: Box::new(move |scope| Rc::new(handler(scope))),
+            connect: Box::new(move |object, slot| connect(object, slot, PhantomData)),
+        }
+    }
+}
+
+/// Give a signal handler closure the type expected by the `connect_*` method
+/// it's going to be connected through.
+///
+/// Closures need to know their argument types where they're declared, and
+/// handler closures are declared before they get anywhere near a `connect_*`
+/// call, so we borrow the type from the method signature here.
+pub fn signal_handler<O, F, C>(_connect: C, handler: F) -> F
+where
+    C: Fn(&O, F) -> SignalHandlerId,
+{
+    handler
+}
+
+/// A value returned from a signal handler declared in the [`gtk!`][gtk!] macro.
+///
+/// Usually, a signal handler just returns a message for its component, but some signals expect
+/// their handlers to return a value to GTK as well, like the [`Inhibit`][Inhibit] returned
+/// from `delete_event` which tells GTK whether to stop a window from closing. A handler for one of
+/// these can return a tuple of a message and the return value, or of an `Option` of a message, if
+/// it doesn't always need to send one:
+///
+/// ```rust,no_run
+/// # use vgtk::{gtk, VNode, Component};
+/// # use vgtk::lib::gtk::{Inhibit, Window, WidgetExt};
+/// # #[derive(Clone, Debug)] enum Message { AskToClose }
+/// # #[derive(Default)] struct Comp { saved: bool }
+/// # impl Component for Comp { type Message = Message; type Properties = (); fn view(&self) -> VNode<Self> {
+/// # let saved = self.saved;
+/// gtk! {
+///     <Window on delete_event=|_, _| {
+///         if saved {
+///             (None, Inhibit(false))
+///         } else {
+///             (Some(Message::AskToClose), Inhibit(true))
+///         }
+///     } />
+/// }
+/// # }}
+/// ```
+///
+/// A handler which returns just a message, or an `Option` of one, will return the default value to
+/// GTK, which is `Inhibit(false)` for signals expecting an [`Inhibit`][Inhibit]. An `async` handler
+/// always returns the default value, as it has to return before its message is ready, so it can
+/// only return a message or an `Option` of one.
+///
+/// [gtk!]: macro.gtk.html
+/// [Inhibit]: ../glib/signal/struct.Inhibit.html
+pub trait HandlerResult<Message, Return> {
+    /// Split the value into an optional message and the value to return to GTK.
+    fn into_parts(self) -> (Option<Message>, Return);
+}
+
+impl<Message, Return: Default> HandlerResult<Message, Return> for Message {
+    fn into_parts(self) -> (Option<Message>, Return) {
+        (Some(self), Default::default())
+    }
+}
+
+impl<Message, Return: Default> HandlerResult<Message, Return> for Option<Message> {
+    fn into_parts(self) -> (Option<Message>, Return) {
+        (self, Default::default())
+    }
+}
+
+impl<Message, Return> HandlerResult<Message, Return> for (Message, Return) {
+    fn into_parts(self) -> (Option<Message>, Return) {
+        (Some(self.0), self.1)
+    }
+}
+
+impl<Message, Return> HandlerResult<Message, Return> for (Option<Message>, Return) {
+    fn into_parts(self) -> (Option<Message>, Return) {
+        self
+    }
+}
+
+/// Send the message from a signal handler's result, if there is one, and
+/// return the rest to GTK.
+pub fn handler_result<Model, Return, Result>(scope: &Scope<Model>, result: Result) -> Return
+where
+    Model: 'static + Component,
+    Result: HandlerResult<Model::Message, Return>,
+{
+    let (msg, value) = result.into_parts();
+    if let Some(msg) = msg {
+        scope.send_message(msg);
+    }
+    value
+}
+
+/// The handler closure for a connected signal.
+///
+/// Signals are connected only once, to a closure which calls whatever handler is
+/// in the slot when the signal is emitted. Re-rendering replaces the handler in
+/// the slot, so the signal always runs the closure from the latest render.
+pub struct VHandlerSlot(RefCell<Rc<dyn Any>>);
+
+impl VHandlerSlot {
+    pub(crate) fn new(handler: Rc<dyn Any>) -> Self {
+        VHandlerSlot(RefCell::new(handler))
+    }
+
+    pub(crate) fn replace(&self, handler: Rc<dyn Any>) {
+        *self.0.borrow_mut() = handler;
+    }
+
+    /// Test whether a handler closure is of the same type as the one in the slot.
+    pub(crate) fn accepts(&self, handler: &Rc<dyn Any>) -> bool {
+        (**self.0.borrow()).type_id() == (**handler).type_id()
+    }
+
+    pub fn get<F: 'static>(&self, _type: PhantomData<F>) -> Rc<F> {
+        self.0
+            .borrow()
+            .clone()
+            .downcast()
+            .unwrap_or_else(|_| panic!("signal handler slot holds an unexpected type"))
+    }
 }
@@ -1,24 +1,32 @@
-use crate::Component;
+use crate::{Children, Component};
 
 pub(crate) mod component;
 mod gobject;
 mod handler;
 mod property;
+mod reference;
 
+pub use crate::event::{connect_event, event_handler};
 pub use component::{PropTransform, VComponent};
 pub use gobject::VObject;
-pub use handler::VHandler;
-pub use property::VProperty;
+pub use handler::{handler_result, signal_handler, HandlerResult, VHandler, VHandlerSlot};
+pub use property::{VConstruct, VProperty};
+pub use reference::VRef;
 
-/// A node in the virtual component tree representing a [`Component`][Component] or a Gtk widget.
+/// A node in the virtual component tree representing a [`Component`][Component], a Gtk widget,
+/// a fragment, which is a list of nodes to be added to the parent in its place, or a list of
+/// [`Children`][Children] passed in by a parent component.
 ///
 /// Don't attempt to construct these directly. Use the [`gtk!`][gtk!] macro instead.
 ///
 /// [gtk!]: macro.gtk.html
 /// [Component]: trait.Component.html
+/// [Children]: struct.Children.html
 pub enum VNode<Model: Component> {
     Object(VObject<Model>),
     Component(VComponent<Model>),
+    Fragment(Vec<VNode<Model>>),
+    Children(Children),
 }
 
 impl<Model: Component> VNode<Model> {
@@ -26,17 +34,28 @@ impl<Model: Component> VNode<Model> {
         match self {
             VNode::Object(object) => &object.child_props,
             VNode::Component(comp) => &comp.child_props,
+            VNode::Fragment(_) | VNode::Children(_) => &[],
         }
     }
 
-    pub(crate) fn get_child_prop(&self, name: &str) -> Option<&VProperty> {
-        let props = self.get_child_props();
-        for prop in props {
-            if prop.name == name {
-                return Some(prop);
+    pub(crate) fn get_key(&self) -> Option<&str> {
+        match self {
+            VNode::Object(object) => object.key.as_deref(),
+            VNode::Component(comp) => comp.key.as_deref(),
+            VNode::Fragment(_) | VNode::Children(_) => None,
+        }
+    }
+
+    /// Collect a list of nodes, replacing any fragments with their contents.
+    pub(crate) fn flatten(nodes: &[VNode<Model>]) -> Vec<&VNode<Model>> {
+        let mut out = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            match node {
+                VNode::Fragment(children) => out.extend(VNode::flatten(children)),
+                node => out.push(node),
             }
         }
-        None
+        out
     }
 }
 
@@ -1,6 +1,43 @@
-use glib::Object;
+use glib::{Object, Value};
 
+use std::any::Any;
+use std::rc::Rc;
+
+#[derive(Clone)]
 pub struct VProperty {
     pub name: &'static str,
-    pub set: Box<dyn Fn(&Object, Option<&Object>, bool) + 'static>,
+    pub value: Rc<dyn Any>,
+    pub set: Rc<dyn Fn(&Object, Option<&Object>, Option<&dyn Any>) + 'static>,
+    pub construct: Option<VConstruct>,
+    /// Undo the effect of a previous version of this property on an object, for
+    /// properties which need to be cleaned up when they're no longer rendered.
+    /// It's given the object, its parent and the previous value.
+    pub unset: Option<Rc<dyn Fn(&Object, Option<&Object>, &dyn Any) + 'static>>,
+}
+
+impl VProperty {
+    /// Test whether going from the previous version of this property to this
+    /// one means the object has to be rebuilt.
+    pub(crate) fn needs_rebuild(&self, previous: Option<&VProperty>) -> bool {
+        match (
+            self.construct.as_ref().and_then(|c| c.rebuild.as_ref()),
+            previous,
+        ) {
+            (Some(changed), Some(previous)) => changed(&*previous.value),
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// A property which can be given to the object when it's constructed.
+#[derive(Clone)]
+pub struct VConstruct {
+    /// The property's GObject name.
+    pub name: &'static str,
+    pub value: Rc<dyn Fn() -> Value>,
+    /// For `construct:` properties, which always go to the constructor, a test
+    /// for whether the value differs from a previous one, in which case the
+    /// object has to be rebuilt.
+    pub rebuild: Option<Rc<dyn Fn(&dyn Any) -> bool>>,
 }
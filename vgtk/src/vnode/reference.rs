@@ -0,0 +1,49 @@
+use glib::{IsA, Object};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::widget_ref::{RefTarget, WidgetRef};
+
+/// A [`WidgetRef`][WidgetRef] given to a node with the `ref` attribute.
+///
+/// [WidgetRef]: ../struct.WidgetRef.html
+#[derive(Clone)]
+pub struct VRef(Rc<RefCell<Option<RefTarget>>>);
+
+impl VRef {
+    /// Construct a `VRef` for an object of type `O`, which must fit in the `WidgetRef`.
+    pub fn new<O: IsA<T>, T: IsA<Object>>(reference: &WidgetRef<T>) -> Self {
+        VRef(reference.target.clone())
+    }
+
+    /// Construct a `VRef` for the root object of a component, which is checked when it's used.
+    pub fn any<T: IsA<Object>>(reference: &WidgetRef<T>) -> Self {
+        VRef(reference.target.clone())
+    }
+
+    pub(crate) fn attach(&self, target: RefTarget) {
+        *self.0.borrow_mut() = Some(target);
+    }
+
+    /// Empty the reference, unless something else has taken it over since.
+    pub(crate) fn detach(&self, target: &RefTarget) {
+        let mut current = self.0.borrow_mut();
+        if current.as_ref().map(|current| current.is(target)) == Some(true) {
+            *current = None;
+        }
+    }
+
+    /// Move the reference held by a node's state over to its new spec.
+    pub(crate) fn update(current: &mut Option<VRef>, new: Option<&VRef>, target: RefTarget) {
+        if let Some(old) = current.take() {
+            if new.map(|new| Rc::ptr_eq(&old.0, &new.0)) != Some(true) {
+                old.detach(&target);
+            }
+        }
+        if let Some(new) = new {
+            new.attach(target);
+            *current = Some(new.clone());
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use glib::{Cast, IsA, Object, ObjectExt};
+
+use std::cell::RefCell;
+use std::fmt::{Debug, Error, Formatter};
+use std::marker::PhantomData;
+use std::rc::{Rc, Weak};
+
+use crate::vdom::Placement;
+
+/// A handle to a GTK object rendered by the [`gtk!`][gtk!] macro.
+///
+/// Sometimes you need to call a method on a widget which doesn't map to a property, like
+/// [`grab_focus()`][grab_focus] on an [`Entry`][Entry]. Keep a `WidgetRef` in your component
+/// and give it to the widget using the `ref` attribute:
+///
+/// ```rust,no_run
+/// # use vgtk::{gtk, VNode, Component, UpdateAction, WidgetRef};
+/// # use vgtk::lib::gtk::{Box, Button, ButtonExt, Entry, WidgetExt};
+/// #[derive(Clone, Debug, Default)]
+/// pub struct Search {
+///     entry: WidgetRef<Entry>,
+/// }
+///
+/// #[derive(Clone, Debug)]
+/// pub enum Message {
+///     Focus,
+/// }
+///
+/// impl Component for Search {
+///     type Message = Message;
+///     type Properties = ();
+///
+///     fn update(&mut self, message: Message) -> UpdateAction<Self> {
+///         match message {
+///             Message::Focus => {
+///                 if let Some(entry) = self.entry.get() {
+///                     entry.grab_focus();
+///                 }
+///                 UpdateAction::None
+///             }
+///         }
+///     }
+///
+///     fn view(&self) -> VNode<Self> {
+///         gtk! {
+///             <Box>
+///                 <Entry ref=self.entry />
+///                 <Button label="Search" on clicked=|_| Message::Focus />
+///             </Box>
+///         }
+///     }
+/// }
+/// ```
+///
+/// The handle is filled in when the widget is built, and emptied again when it's destroyed. The
+/// macro checks that the widget fits in the handle, so you can use a `WidgetRef<Widget>` for an
+/// `Entry`, but not a `WidgetRef<Button>`.
+///
+/// You can also give a `ref` to a subcomponent, as in `<@Search ref=self.search />`, to get at the
+/// root widget of its view. This will follow the root widget if the subcomponent replaces it,
+/// but because the type of the root widget isn't known until runtime, [`get()`][get] will panic
+/// if it doesn't fit in the handle.
+///
+/// `WidgetRef`s are cheap to clone, and all clones refer to the same object.
+///
+/// [gtk!]: macro.gtk.html
+/// [get]: #method.get
+/// [Entry]: ../gtk/struct.Entry.html
+/// [grab_focus]: ../gtk/trait.WidgetExt.html#tymethod.grab_focus
+pub struct WidgetRef<T> {
+    pub(crate) target: Rc<RefCell<Option<RefTarget>>>,
+    object_type: PhantomData<T>,
+}
+
+impl<T: IsA<Object>> WidgetRef<T> {
+    /// Construct an empty `WidgetRef`.
+    pub fn new() -> Self {
+        WidgetRef {
+            target: Default::default(),
+            object_type: PhantomData,
+        }
+    }
+
+    /// Get the object this `WidgetRef` refers to, if it's currently rendered.
+    pub fn get(&self) -> Option<T> {
+        Some(self.get_object()?.downcast().unwrap_or_else(|object| {
+            panic!(
+                "WidgetRef<{}> cannot refer to a {}",
+                T::static_type(),
+                object.get_type()
+            )
+        }))
+    }
+
+    /// Test whether the `WidgetRef` currently refers to an object.
+    pub fn is_empty(&self) -> bool {
+        self.get_object().is_none()
+    }
+
+    fn get_object(&self) -> Option<Object> {
+        self.target.borrow().as_ref()?.object()
+    }
+}
+
+impl<T: IsA<Object>> Default for WidgetRef<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for WidgetRef<T> {
+    fn clone(&self) -> Self {
+        WidgetRef {
+            target: self.target.clone(),
+            object_type: PhantomData,
+        }
+    }
+}
+
+impl<T: IsA<Object>> PartialEq for WidgetRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.target, &other.target)
+    }
+}
+
+impl<T: IsA<Object>> Debug for WidgetRef<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self.get_object() {
+            Some(object) => write!(f, "WidgetRef({})", object.get_type()),
+            None => write!(f, "WidgetRef()"),
+        }
+    }
+}
+
+/// What a `WidgetRef` is pointing at.
+///
+/// A component's root objects can change while it lives, so for a component
+/// we keep its placement and look up its first object when asked.
+pub(crate) enum RefTarget {
+    Object(Object),
+    Component(Weak<Placement>),
+}
+
+impl RefTarget {
+    fn object(&self) -> Option<Object> {
+        match self {
+            RefTarget::Object(object) => Some(object.clone()),
+            RefTarget::Component(placement) => {
+                placement.upgrade()?.objects.borrow().first().cloned()
+            }
+        }
+    }
+
+    pub(crate) fn is(&self, other: &RefTarget) -> bool {
+        match (self, other) {
+            (RefTarget::Object(left), RefTarget::Object(right)) => left == right,
+            (RefTarget::Component(left), RefTarget::Component(right)) => left.ptr_eq(right),
+            _ => false,
+        }
+    }
+}